@@ -0,0 +1,162 @@
+use crate::error::{Entity, StoreError};
+use crate::inventory::Product;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeType {
+    String,
+    Number,
+    Bool,
+    Date,
+}
+
+impl AttributeType {
+    pub fn parse(&self, value: &str) -> Result<AttributeValue, String> {
+        let value = value.trim();
+        match self {
+            AttributeType::String => Ok(AttributeValue::String(value.to_string())),
+            AttributeType::Number => Decimal::from_str(value)
+                .map(|number| AttributeValue::Number(number.normalize()))
+                .map_err(|_| format!("Invalid number: {value}")),
+            AttributeType::Bool => match value.to_ascii_lowercase().as_str() {
+                "true" | "yes" => Ok(AttributeValue::Bool(true)),
+                "false" | "no" => Ok(AttributeValue::Bool(false)),
+                _ => Err(format!("Invalid boolean: {value}, expected true or false")),
+            },
+            AttributeType::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(AttributeValue::Date)
+                .map_err(|_| format!("Invalid date: {value}, expected YYYY-MM-DD")),
+        }
+    }
+}
+
+impl fmt::Display for AttributeType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            AttributeType::String => "string",
+            AttributeType::Number => "number",
+            AttributeType::Bool => "bool",
+            AttributeType::Date => "date",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+// Values are stored with their type, e.g. {"type": "date", "value": "2025-07-31"}, so a
+// string that looks like a date or number reads back as a string.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum AttributeValue {
+    String(String),
+    Number(Decimal),
+    Bool(bool),
+    Date(NaiveDate),
+}
+
+impl AttributeValue {
+    pub fn kind(&self) -> AttributeType {
+        match self {
+            AttributeValue::String(_) => AttributeType::String,
+            AttributeValue::Number(_) => AttributeType::Number,
+            AttributeValue::Bool(_) => AttributeType::Bool,
+            AttributeValue::Date(_) => AttributeType::Date,
+        }
+    }
+}
+
+impl fmt::Display for AttributeValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeValue::String(value) => write!(f, "{value}"),
+            AttributeValue::Number(value) => write!(f, "{value}"),
+            AttributeValue::Bool(value) => write!(f, "{value}"),
+            AttributeValue::Date(value) => write!(f, "{}", value.format("%Y-%m-%d")),
+        }
+    }
+}
+
+// An entry of the store's attribute schema. Products may only hold attributes that are
+// defined here, with values of the defined type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeDefinition {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: AttributeType,
+}
+
+pub trait AttributeSchema {
+    fn define_attribute(&mut self, name: &str, kind: AttributeType) -> Result<(), StoreError>;
+    fn remove_attribute(&mut self, name: &str, inventory: &[Product]) -> Result<(), StoreError>;
+    fn get_attribute(&self, name: &str) -> Option<&AttributeDefinition>;
+}
+
+impl AttributeSchema for Vec<AttributeDefinition> {
+    fn define_attribute(&mut self, name: &str, kind: AttributeType) -> Result<(), StoreError> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > 50 {
+            return Err(StoreError::invalid_field(
+                "name",
+                "Attribute name must be 1-50 characters",
+            ));
+        }
+        if self.get_attribute(name).is_some() {
+            return Err(StoreError::already_exists(Entity::Attribute, name));
+        }
+
+        self.push(AttributeDefinition {
+            name: name.to_string(),
+            kind,
+        });
+        Ok(())
+    }
+
+    fn remove_attribute(&mut self, name: &str, inventory: &[Product]) -> Result<(), StoreError> {
+        let index = self
+            .iter()
+            .position(|a| a.name == name)
+            .ok_or_else(|| StoreError::not_found(Entity::Attribute, name))?;
+        if let Some(product) = inventory.iter().find(|p| p.attributes.contains_key(name)) {
+            return Err(StoreError::invalid(format!(
+                "Attribute {name} is still set on product {} and cannot be removed",
+                product.name
+            )));
+        }
+
+        self.remove(index);
+        Ok(())
+    }
+
+    fn get_attribute(&self, name: &str) -> Option<&AttributeDefinition> {
+        self.iter().find(|a| a.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_attribute_values() {
+        assert_eq!(AttributeType::Number.parse("1.50").unwrap(), AttributeValue::Number(Decimal::new(15, 1)));
+        assert_eq!(AttributeType::Bool.parse("yes").unwrap(), AttributeValue::Bool(true));
+        assert!(AttributeType::Date.parse("31/07/2025").is_err());
+        let value = AttributeType::String.parse("2025-07-31").unwrap();
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<AttributeValue>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_define_attribute() {
+        let mut schema: Vec<AttributeDefinition> = Vec::new();
+        schema.define_attribute("brand", AttributeType::String).unwrap();
+        assert!(schema.define_attribute("brand", AttributeType::Number).is_err());
+        schema.remove_attribute("brand", &[]).unwrap();
+        assert!(schema.get_attribute("brand").is_none());
+    }
+}
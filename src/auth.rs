@@ -1,30 +1,73 @@
+use crate::error::StoreError;
+use std::env;
 use std::io::{self, Write};
 
 pub struct Auth;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    Manager,
+    Clerk,
+}
+
+// The clerk login is only accepted when the store has set a password for it, so no clerk
+// password is built in.
+const CLERK_PASSWORD_VAR: &str = "STORE_CLERK_PASSWORD";
+
 impl Auth {
-    pub fn authenticate() -> Result<(), String> {
-        println!("Please login to continue");
+    pub fn authenticate() -> Result<Role, StoreError> {
+        eprintln!("Please login to continue");
 
         let username = Self::read_input("Username:")?;
         let password = Self::read_input("Password:")?;
+        let clerk_password = env::var(CLERK_PASSWORD_VAR).ok();
+        Self::check(&username, &password, clerk_password.as_deref())
+    }
 
-        if username == "store_manager" && password == "pass1234" {
-            Ok(())
-        } else {
-            Err("Invalid username or password".to_string())
+    fn check(
+        username: &str,
+        password: &str,
+        clerk_password: Option<&str>,
+    ) -> Result<Role, StoreError> {
+        match (username, password) {
+            ("store_manager", "pass1234") => Ok(Role::Manager),
+            ("store_clerk", password)
+                if clerk_password.is_some_and(|clerk| !clerk.is_empty() && clerk == password) =>
+            {
+                Ok(Role::Clerk)
+            }
+            _ => Err(StoreError::Unauthorized(
+                "Invalid username or password".to_string(),
+            )),
         }
     }
 
-    fn read_input(prompt: &str) -> Result<String, String> {
-        print!("{prompt} ");
-        io::stdout()
-            .flush()
-            .map_err(|_| "Failed to flush stdout. Please try again")?;
+    // Failing to read the login, including stdin closing before it was given, is an I/O
+    // error rather than a failed login, so it exits with the storage class's code.
+    fn read_input(prompt: &str) -> Result<String, StoreError> {
+        let io_error = |err: io::Error| StoreError::Storage(format!("Login: {err}"));
+        eprint!("{prompt} ");
+        io::stderr().flush().map_err(io_error)?;
         let mut input = String::new();
-        io::stdin()
-            .read_line(&mut input)
-            .map_err(|err| err.to_string())?;
+        if io::stdin().read_line(&mut input).map_err(io_error)? == 0 {
+            return Err(StoreError::Storage(
+                "Login: input ended before a username and password were given".to_string(),
+            ));
+        }
         Ok(input.trim().to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_login() {
+        assert_eq!(Auth::check("store_manager", "pass1234", None), Ok(Role::Manager));
+        assert_eq!(Auth::check("store_clerk", "", None).unwrap_err().exit_code(), 3);
+        assert_eq!(Auth::check("store_clerk", "", Some("")).unwrap_err().exit_code(), 3);
+        assert_eq!(Auth::check("store_clerk", "till-42", Some("till-42")), Ok(Role::Clerk));
+        assert!(Auth::check("store_clerk", "pass1234", Some("till-42")).is_err());
+    }
+}
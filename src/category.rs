@@ -0,0 +1,292 @@
+use crate::error::{Entity, StoreError};
+use crate::inventory::{Inventory, Product, name_key};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// A node in the category tree. Names are unique across the whole tree, compared like
+// product names, so products refer to their category by name alone.
+#[derive(Validate, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Category {
+    #[validate(length(min = 1, max = 50, message = "Category name must be 1-50 characters"))]
+    pub name: String,
+    #[serde(default)]
+    pub parent: Option<String>,
+}
+
+pub trait Categories {
+    fn add_category(&mut self, name: &str, parent: Option<&str>) -> Result<(), StoreError>;
+    fn rename_category(
+        &mut self,
+        name: &str,
+        new_name: &str,
+        inventory: &mut impl Inventory,
+    ) -> Result<(), StoreError>;
+    fn move_category(&mut self, name: &str, parent: Option<&str>) -> Result<(), StoreError>;
+    fn remove_category(&mut self, name: &str, inventory: &[Product]) -> Result<(), StoreError>;
+    fn get_category(&self, name: &str) -> Option<&Category>;
+    fn tree(&self) -> Vec<(usize, &Category)>;
+    fn with_subcategories(&self, name: &str) -> Result<Vec<String>, StoreError>;
+}
+
+// A new category name, trimmed. The name cannot contain the separator of category paths,
+// or filters by path could not tell where it ends.
+fn checked_name(name: &str) -> Result<&str, StoreError> {
+    let name = name.trim();
+    if name.contains(" > ") {
+        return Err(StoreError::invalid_field(
+            "name",
+            format!("Category name {name} cannot contain \" > \""),
+        ));
+    }
+    Ok(name)
+}
+
+fn category_position(categories: &[Category], name: &str) -> Option<usize> {
+    let key = name_key(name);
+    categories.iter().position(|c| name_key(&c.name) == key)
+}
+
+pub fn find_category<'a>(categories: &'a [Category], name: &str) -> Option<&'a Category> {
+    category_position(categories, name).map(|index| &categories[index])
+}
+
+// The name a category is saved under, for a name given in any case or Unicode form.
+fn stored_name(categories: &[Category], name: &str) -> Result<String, StoreError> {
+    find_category(categories, name)
+        .map(|c| c.name.clone())
+        .ok_or_else(|| StoreError::not_found(Entity::Category, name))
+}
+
+impl Categories for Vec<Category> {
+    fn add_category(&mut self, name: &str, parent: Option<&str>) -> Result<(), StoreError> {
+        let name = checked_name(name)?;
+        if let Some(existing) = self.get_category(name) {
+            return Err(StoreError::already_exists(Entity::Category, &existing.name));
+        }
+        let parent = parent.map(|parent| stored_name(self, parent)).transpose()?;
+
+        let category = Category {
+            name: name.to_string(),
+            parent,
+        };
+        category.validate()?;
+
+        self.push(category);
+        Ok(())
+    }
+
+    fn rename_category(
+        &mut self,
+        name: &str,
+        new_name: &str,
+        inventory: &mut impl Inventory,
+    ) -> Result<(), StoreError> {
+        let index = category_position(self, name)
+            .ok_or_else(|| StoreError::not_found(Entity::Category, name))?;
+        let name = self[index].name.clone();
+        let new_name = checked_name(new_name)?;
+        if name == new_name {
+            return Ok(());
+        }
+        // A rename that only changes the case of the name finds the category itself.
+        if let Some(existing) = self.get_category(new_name)
+            && existing.name != name
+        {
+            return Err(StoreError::already_exists(Entity::Category, &existing.name));
+        }
+
+        let renamed = Category {
+            name: new_name.to_string(),
+            parent: self[index].parent.clone(),
+        };
+        renamed.validate()?;
+
+        self[index] = renamed;
+        self.iter_mut()
+            .filter(|c| c.parent.as_deref() == Some(name.as_str()))
+            .for_each(|c| c.parent = Some(new_name.to_string()));
+        for index in 0..inventory.products().len() {
+            if inventory.products()[index].category.as_deref() == Some(name.as_str()) {
+                inventory.update_product(index, |p| p.category = Some(new_name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn move_category(&mut self, name: &str, parent: Option<&str>) -> Result<(), StoreError> {
+        let name = stored_name(self, name)?;
+        let parent = parent.map(|parent| stored_name(self, parent)).transpose()?;
+        if let Some(parent) = &parent {
+            // Walking up from the new parent must not pass through the category itself.
+            let mut ancestor = Some(parent.as_str());
+            while let Some(current) = ancestor {
+                if current == name {
+                    return Err(StoreError::invalid(format!(
+                        "Category {name} cannot be moved under itself"
+                    )));
+                }
+                ancestor = self.get_category(current).and_then(|c| c.parent.as_deref());
+            }
+        }
+
+        if let Some(category) = self.iter_mut().find(|c| c.name == name) {
+            category.parent = parent;
+        }
+        Ok(())
+    }
+
+    fn remove_category(&mut self, name: &str, inventory: &[Product]) -> Result<(), StoreError> {
+        let index = category_position(self, name)
+            .ok_or_else(|| StoreError::not_found(Entity::Category, name))?;
+        let name = self[index].name.clone();
+        let name = name.as_str();
+        if self.iter().any(|c| c.parent.as_deref() == Some(name)) {
+            return Err(StoreError::invalid(format!(
+                "Category {name} has subcategories and cannot be removed"
+            )));
+        }
+        if inventory
+            .iter()
+            .any(|p| p.category.as_deref() == Some(name))
+        {
+            return Err(StoreError::invalid(format!(
+                "Category {name} has products and cannot be removed"
+            )));
+        }
+
+        self.remove(index);
+        Ok(())
+    }
+
+    fn get_category(&self, name: &str) -> Option<&Category> {
+        find_category(self, name)
+    }
+
+    // Categories in depth-first order with their depth, children sorted by name.
+    fn tree(&self) -> Vec<(usize, &Category)> {
+        fn visit<'a>(
+            categories: &'a [Category],
+            parent: Option<&str>,
+            depth: usize,
+            tree: &mut Vec<(usize, &'a Category)>,
+        ) {
+            let mut children: Vec<&Category> = categories
+                .iter()
+                .filter(|c| c.parent.as_deref() == parent)
+                .collect();
+            children.sort_by(|a, b| a.name.cmp(&b.name));
+            for child in children {
+                tree.push((depth, child));
+                visit(categories, Some(&child.name), depth + 1, tree);
+            }
+        }
+
+        let mut tree = Vec::new();
+        visit(self, None, 0, &mut tree);
+        tree
+    }
+
+    // The category followed by every category below it, for filters that select a whole
+    // branch of the tree.
+    fn with_subcategories(&self, name: &str) -> Result<Vec<String>, StoreError> {
+        let name = stored_name(self, name)?;
+        Ok(self
+            .tree()
+            .into_iter()
+            .map(|(_, c)| c.name.clone())
+            .filter(|c| category_path(self, c).split(" > ").any(|p| p == name))
+            .collect())
+    }
+}
+
+// Full path of a category from the root, e.g. "Produce > Citrus".
+pub fn category_path(categories: &[Category], name: &str) -> String {
+    let find = |name: &str| categories.iter().find(|c| c.name == name);
+    let mut names = vec![name];
+    let mut current = find(name);
+    while let Some(parent) = current.and_then(|c| c.parent.as_deref()) {
+        if names.contains(&parent) {
+            break;
+        }
+        names.push(parent);
+        current = find(parent);
+    }
+    names.reverse();
+    names.join(" > ")
+}
+
+// Resolves the category path of the product behind a sale or purchase, for reports that
+// group records by category.
+pub struct CategoryLookup<'a> {
+    categories: &'a [Category],
+    inventory: &'a [Product],
+}
+
+impl<'a> CategoryLookup<'a> {
+    pub fn new(categories: &'a [Category], inventory: &'a [Product]) -> Self {
+        CategoryLookup {
+            categories,
+            inventory,
+        }
+    }
+
+    pub fn category_of(&self, product_name: &str) -> Option<String> {
+        self.inventory
+            .iter()
+            .find(|p| p.matches(product_name))
+            .and_then(|p| p.category.as_deref())
+            .map(|category| category_path(self.categories, category))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn categories() -> Vec<Category> {
+        let mut categories = Vec::new();
+        categories.add_category("Produce", None).unwrap();
+        categories.add_category("Citrus", Some("Produce")).unwrap();
+        categories.add_category("Beverages", None).unwrap();
+        categories
+    }
+
+    #[test]
+    fn test_category_path() {
+        let categories = categories();
+        assert_eq!(category_path(&categories, "Citrus"), "Produce > Citrus");
+        let names: Vec<&str> = categories.tree().iter().map(|(_, c)| c.name.as_str()).collect();
+        assert_eq!(names, vec!["Beverages", "Produce", "Citrus"]);
+        assert_eq!(categories.with_subcategories("Produce").unwrap(), vec!["Produce", "Citrus"]);
+    }
+
+    #[test]
+    fn test_move_category_under_itself() {
+        let mut categories = categories();
+        assert!(categories.move_category("Produce", Some("Citrus")).is_err());
+        categories.move_category("Citrus", Some("Beverages")).unwrap();
+        assert_eq!(category_path(&categories, "Citrus"), "Beverages > Citrus");
+    }
+
+    #[test]
+    fn test_category_names() {
+        let mut categories = categories();
+        assert_eq!(categories.add_category("produce ", None).unwrap_err().exit_code(), 5);
+        assert!(matches!(categories.add_category("Fruit > Citrus", None), Err(StoreError::Validation(_))));
+        categories.add_category("  Dairy ", Some("produce")).unwrap();
+        assert_eq!(categories.get_category("DAIRY").unwrap().name, "Dairy");
+        assert_eq!(category_path(&categories, "Dairy"), "Produce > Dairy");
+        assert!(categories.rename_category("Dairy", "Produce > Milk", &mut Vec::new()).is_err());
+        categories.rename_category("dairy", "DAIRY", &mut Vec::new()).unwrap();
+        assert_eq!(categories.with_subcategories("PRODUCE").unwrap(), vec!["Produce", "Citrus", "DAIRY"]);
+    }
+
+    #[test]
+    fn test_remove_category_with_subcategories() {
+        let mut categories = categories();
+        assert!(categories.remove_category("Produce", &[]).is_err());
+        categories.remove_category("Citrus", &[]).unwrap();
+        categories.remove_category("Produce", &[]).unwrap();
+        assert!(categories.get_category("Produce").is_none());
+    }
+}
@@ -0,0 +1,184 @@
+use crate::inventory::Product;
+use crate::money::Money;
+use crate::purchase::Purchase;
+use crate::units::Quantity;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum CostingMethod {
+    #[default]
+    WeightedAverage,
+    Fifo,
+    Lifo,
+}
+
+impl fmt::Display for CostingMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self {
+            CostingMethod::WeightedAverage => "weighted average",
+            CostingMethod::Fifo => "FIFO",
+            CostingMethod::Lifo => "LIFO",
+        };
+        write!(f, "{method}")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CostLayer {
+    pub quantity: Quantity,
+    pub unit_cost: Money,
+}
+
+pub fn layers_value(layers: &[CostLayer]) -> Money {
+    layers.iter().map(|l| l.unit_cost * l.quantity).sum()
+}
+
+pub fn layers_quantity(layers: &[CostLayer]) -> Quantity {
+    layers.iter().map(|l| l.quantity).sum()
+}
+
+// Adds or removes stock so the layers hold exactly `quantity` units. Missing units are
+// layered as new stock at `unit_cost`, and surplus units come off the layers `method`
+// would sell first.
+pub fn reconcile_layers(
+    layers: &mut Vec<CostLayer>,
+    quantity: Quantity,
+    unit_cost: Money,
+    method: CostingMethod,
+) {
+    let layered = layers_quantity(layers);
+    if layered < quantity {
+        layers.push(CostLayer {
+            quantity: quantity - layered,
+            unit_cost,
+        });
+    } else if layered > quantity {
+        consume_layers(layers, layered - quantity, method);
+    }
+}
+
+// Removes `quantity` units from the layers and returns their total cost, rounded to cents.
+// The caller is responsible for checking that enough stock is layered.
+pub fn consume_layers(
+    layers: &mut Vec<CostLayer>,
+    quantity: Quantity,
+    method: CostingMethod,
+) -> Money {
+    if method == CostingMethod::WeightedAverage {
+        let total_quantity = layers_quantity(layers);
+        if total_quantity.is_zero() {
+            return Money::ZERO;
+        }
+        let average = layers_value(layers).per_unit(total_quantity);
+        let remaining = total_quantity - quantity.min(total_quantity);
+        layers.clear();
+        if remaining > Quantity::ZERO {
+            layers.push(CostLayer {
+                quantity: remaining,
+                unit_cost: average,
+            });
+        }
+        return average.times(quantity.min(total_quantity));
+    }
+
+    let mut remaining = quantity;
+    let mut cost = Money::ZERO;
+    while remaining > Quantity::ZERO && !layers.is_empty() {
+        let index = match method {
+            CostingMethod::Lifo => layers.len() - 1,
+            _ => 0,
+        };
+        let layer = &mut layers[index];
+        let taken = layer.quantity.min(remaining);
+        cost += layer.unit_cost * taken;
+        layer.quantity -= taken;
+        remaining -= taken;
+        if layer.quantity.is_zero() {
+            layers.remove(index);
+        }
+    }
+    cost.round_cents()
+}
+
+// Rebuilds the layers still on hand from purchase history, assuming the units left in
+// stock are the most recently purchased ones. Units not covered by purchases are layered
+// at the product's cost price.
+pub fn layers_from_purchases(product: &Product, purchases: &[Purchase]) -> Vec<CostLayer> {
+    let mut layers = Vec::new();
+    let mut remaining = product.quantity;
+    for purchase in purchases
+        .iter()
+        .rev()
+        .filter(|p| product.matches(&p.product_name))
+    {
+        if remaining.is_zero() {
+            break;
+        }
+        let taken = purchase.quantity.min(remaining);
+        layers.insert(
+            0,
+            CostLayer {
+                quantity: taken,
+                unit_cost: purchase.base_purchase_price,
+            },
+        );
+        remaining -= taken;
+    }
+    if remaining > Quantity::ZERO {
+        layers.insert(
+            0,
+            CostLayer {
+                quantity: remaining,
+                unit_cost: product.cost_price,
+            },
+        );
+    }
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layers() -> Vec<CostLayer> {
+        vec![
+            CostLayer { quantity: 10.into(), unit_cost: Money::from_cents(100) },
+            CostLayer { quantity: 10.into(), unit_cost: Money::from_cents(200) },
+        ]
+    }
+
+    #[test]
+    fn test_consume_layers_fifo() {
+        let mut layers = layers();
+        assert_eq!(consume_layers(&mut layers, 15.into(), CostingMethod::Fifo), Money::from_cents(2000));
+        assert_eq!(layers, vec![CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(200) }]);
+    }
+
+    #[test]
+    fn test_consume_layers_lifo() {
+        let mut layers = layers();
+        assert_eq!(consume_layers(&mut layers, 15.into(), CostingMethod::Lifo), Money::from_cents(2500));
+        assert_eq!(layers, vec![CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(100) }]);
+    }
+
+    #[test]
+    fn test_consume_layers_weighted_average() {
+        let mut layers = layers();
+        assert_eq!(consume_layers(&mut layers, 10.into(), CostingMethod::WeightedAverage), Money::from_cents(1500));
+        assert_eq!(layers, vec![CostLayer { quantity: 10.into(), unit_cost: Money::from_cents(150) }]);
+    }
+
+    #[test]
+    fn test_reconcile_layers_by_method() {
+        let mut layers = layers();
+        reconcile_layers(&mut layers, 15.into(), Money::from_cents(300), CostingMethod::Lifo);
+        assert_eq!(layers, vec![CostLayer { quantity: 10.into(), unit_cost: Money::from_cents(100) }, CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(200) }]);
+        reconcile_layers(&mut layers, 10.into(), Money::from_cents(300), CostingMethod::Fifo);
+        assert_eq!(layers, vec![CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(100) }, CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(200) }]);
+        reconcile_layers(&mut layers, 12.into(), Money::from_cents(300), CostingMethod::Lifo);
+        assert_eq!(layers.last(), Some(&CostLayer { quantity: 2.into(), unit_cost: Money::from_cents(300) }));
+    }
+}
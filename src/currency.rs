@@ -0,0 +1,104 @@
+use crate::error::{Entity, StoreError};
+use crate::money::Money;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+pub fn parse_currency(code: &str) -> Result<String, String> {
+    let code = code.trim().to_ascii_uppercase();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(code)
+    } else {
+        Err(format!("Invalid currency code: {code}"))
+    }
+}
+
+// One unit of `currency` is worth `rate` units of the store's base currency from `date`
+// until the next rate for the same currency.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeRate {
+    pub currency: String,
+    pub date: NaiveDate,
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    pub currency: String,
+    pub rate: Decimal,
+}
+
+impl Conversion {
+    pub fn base(currency: &str) -> Self {
+        Conversion {
+            currency: currency.to_string(),
+            rate: Decimal::ONE,
+        }
+    }
+
+    pub fn to_base(&self, amount: Money) -> Money {
+        amount.convert(self.rate)
+    }
+
+    pub fn to_local(&self, amount: Money) -> Money {
+        amount.convert(Decimal::ONE / self.rate)
+    }
+}
+
+pub fn find_conversion(
+    rates: &[ExchangeRate],
+    currency: &str,
+    base_currency: &str,
+    at: DateTime<Utc>,
+) -> Result<Conversion, StoreError> {
+    if currency == base_currency {
+        return Ok(Conversion::base(base_currency));
+    }
+
+    let date = at.date_naive();
+    rates
+        .iter()
+        .filter(|r| r.currency == currency && r.date <= date)
+        .max_by_key(|r| r.date)
+        .filter(|r| r.rate > Decimal::ZERO)
+        .map(|r| Conversion {
+            currency: currency.to_string(),
+            rate: r.rate,
+        })
+        .ok_or_else(|| {
+            StoreError::not_found(
+                Entity::ExchangeRate,
+                &format!("{currency} on or before {date}"),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn rates() -> Vec<ExchangeRate> {
+        vec![
+            ExchangeRate { currency: "EUR".to_string(), date: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(), rate: Decimal::new(110, 2) },
+            ExchangeRate { currency: "EUR".to_string(), date: NaiveDate::from_ymd_opt(2025, 6, 1).unwrap(), rate: Decimal::new(120, 2) },
+        ]
+    }
+
+    #[test]
+    fn test_find_conversion_uses_rate_on_date() {
+        let at = Utc.with_ymd_and_hms(2025, 3, 15, 12, 0, 0).unwrap();
+        let conversion = find_conversion(&rates(), "EUR", "USD", at).unwrap();
+        assert_eq!(conversion.rate, Decimal::new(110, 2));
+        assert_eq!(conversion.to_base(Money::from_cents(1000)), Money::from_cents(1100));
+    }
+
+    #[test]
+    fn test_find_conversion_without_rate() {
+        let at = Utc.with_ymd_and_hms(2024, 12, 31, 12, 0, 0).unwrap();
+        assert!(find_conversion(&rates(), "EUR", "USD", at).is_err());
+        assert_eq!(find_conversion(&rates(), "USD", "USD", at).unwrap(), Conversion::base("USD"));
+    }
+}
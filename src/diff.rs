@@ -0,0 +1,321 @@
+use crate::error::StoreError;
+use crate::inventory::{Inventory, name_key};
+use crate::store::Store;
+use serde::Serialize;
+use serde_json::Value;
+
+// A field of a record with its value before and after a change.
+#[derive(Debug, Serialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum RecordChange {
+    Added {
+        record: String,
+        value: Value,
+    },
+    Removed {
+        record: String,
+        value: Value,
+    },
+    Changed {
+        record: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+// What a command changed in the store, section by section. Products and categories are
+// matched by name, so a rename shows as one record removed and another added; sales,
+// purchases and merge records are matched by their position in the history.
+#[derive(Debug, Serialize)]
+pub struct StoreDiff {
+    pub inventory: Vec<RecordChange>,
+    pub sales: Vec<RecordChange>,
+    pub purchases: Vec<RecordChange>,
+    pub categories: Vec<RecordChange>,
+    pub merges: Vec<RecordChange>,
+    pub settings: Vec<FieldChange>,
+}
+
+impl StoreDiff {
+    pub fn between(before: &Store, after: &Store) -> Result<Self, StoreError> {
+        Ok(StoreDiff {
+            inventory: by_key(
+                before.inventory.products(),
+                after.inventory.products(),
+                |product| product.name.clone(),
+            )?,
+            sales: by_position(&before.sales, &after.sales, |number, sale| {
+                format!("#{number} {}", sale.product_name)
+            })?,
+            purchases: by_position(&before.purchases, &after.purchases, |number, purchase| {
+                format!("#{number} {}", purchase.product_name)
+            })?,
+            categories: by_key(&before.categories, &after.categories, |category| {
+                category.name.clone()
+            })?,
+            merges: by_position(&before.merges, &after.merges, |number, merge| {
+                format!("#{number} {} into {}", merge.merged, merge.survivor)
+            })?,
+            settings: fields(
+                &serde_json::to_value(&before.settings)?,
+                &serde_json::to_value(&after.settings)?,
+            ),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections()
+            .iter()
+            .all(|(_, changes)| changes.is_empty())
+            && self.settings.is_empty()
+    }
+
+    fn sections(&self) -> [(&'static str, &[RecordChange]); 5] {
+        [
+            ("Inventory", &self.inventory),
+            ("Sales", &self.sales),
+            ("Purchases", &self.purchases),
+            ("Categories", &self.categories),
+            ("Merges", &self.merges),
+        ]
+    }
+
+    // One line per added or removed record, and per changed field of a changed record,
+    // e.g. "  ~ Orange: quantity 50 -> 47".
+    pub fn generate_text(&self) -> String {
+        if self.is_empty() {
+            return "No changes.\n".to_string();
+        }
+        let mut text = String::new();
+        for (section, changes) in self.sections() {
+            if changes.is_empty() {
+                continue;
+            }
+            text.push_str(&format!("{section}:\n"));
+            for change in changes {
+                match change {
+                    RecordChange::Added { record, .. } => text.push_str(&format!("  + {record}\n")),
+                    RecordChange::Removed { record, .. } => {
+                        text.push_str(&format!("  - {record}\n"))
+                    }
+                    RecordChange::Changed { record, fields } => {
+                        for field in fields {
+                            text.push_str(&format!("  ~ {record}: {}\n", describe(field)));
+                        }
+                    }
+                }
+            }
+        }
+        if !self.settings.is_empty() {
+            text.push_str("Settings:\n");
+            for field in &self.settings {
+                text.push_str(&format!("  ~ {}\n", describe(field)));
+            }
+        }
+        text
+    }
+
+    // Tab-separated lines of section, change (+, - or ~), record, and for changed fields
+    // the field with its values before and after.
+    pub fn generate_plain(&self) -> String {
+        let mut text = String::new();
+        for (section, changes) in self.sections() {
+            let section = section.to_lowercase();
+            for change in changes {
+                match change {
+                    RecordChange::Added { record, .. } => {
+                        text.push_str(&format!("{section}\t+\t{record}\n"))
+                    }
+                    RecordChange::Removed { record, .. } => {
+                        text.push_str(&format!("{section}\t-\t{record}\n"))
+                    }
+                    RecordChange::Changed { record, fields } => {
+                        for field in fields {
+                            text.push_str(&format!(
+                                "{section}\t~\t{record}\t{}\t{}\t{}\n",
+                                field.field,
+                                value_text(&field.before),
+                                value_text(&field.after)
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        for field in &self.settings {
+            text.push_str(&format!(
+                "settings\t~\t\t{}\t{}\t{}\n",
+                field.field,
+                value_text(&field.before),
+                value_text(&field.after)
+            ));
+        }
+        text
+    }
+}
+
+fn by_key<T: Serialize>(
+    before: &[T],
+    after: &[T],
+    name: impl Fn(&T) -> String,
+) -> Result<Vec<RecordChange>, StoreError> {
+    let find = |records: &[T], key: &str| {
+        records
+            .iter()
+            .position(|record| name_key(&name(record)) == key)
+    };
+    let mut changes = Vec::new();
+    for old in before {
+        let record = name(old);
+        match find(after, &name_key(&record)) {
+            Some(index) => {
+                let new = serde_json::to_value(&after[index])?;
+                let fields = fields(&serde_json::to_value(old)?, &new);
+                if !fields.is_empty() {
+                    changes.push(RecordChange::Changed { record, fields });
+                }
+            }
+            None => changes.push(RecordChange::Removed {
+                record,
+                value: serde_json::to_value(old)?,
+            }),
+        }
+    }
+    for new in after {
+        let record = name(new);
+        if find(before, &name_key(&record)).is_none() {
+            changes.push(RecordChange::Added {
+                record,
+                value: serde_json::to_value(new)?,
+            });
+        }
+    }
+    Ok(changes)
+}
+
+// Records are labelled by their number in the history, counting from 1.
+fn by_position<T: Serialize>(
+    before: &[T],
+    after: &[T],
+    label: impl Fn(usize, &T) -> String,
+) -> Result<Vec<RecordChange>, StoreError> {
+    let mut changes = Vec::new();
+    for index in 0..before.len().max(after.len()) {
+        let record = |record: &T| label(index + 1, record);
+        match (before.get(index), after.get(index)) {
+            (Some(old), Some(new)) => {
+                let fields = fields(&serde_json::to_value(old)?, &serde_json::to_value(new)?);
+                if !fields.is_empty() {
+                    changes.push(RecordChange::Changed {
+                        record: record(new),
+                        fields,
+                    });
+                }
+            }
+            (Some(old), None) => changes.push(RecordChange::Removed {
+                record: record(old),
+                value: serde_json::to_value(old)?,
+            }),
+            (None, Some(new)) => changes.push(RecordChange::Added {
+                record: record(new),
+                value: serde_json::to_value(new)?,
+            }),
+            (None, None) => {}
+        }
+    }
+    Ok(changes)
+}
+
+// The top-level fields that differ between two serialized records.
+fn fields(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Vec::new();
+    };
+    let mut names: Vec<&String> = before.keys().chain(after.keys()).collect();
+    names.sort();
+    names.dedup();
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old = before.get(name).cloned().unwrap_or(Value::Null);
+            let new = after.get(name).cloned().unwrap_or(Value::Null);
+            (old != new).then(|| FieldChange {
+                field: name.clone(),
+                before: old,
+                after: new,
+            })
+        })
+        .collect()
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn describe(field: &FieldChange) -> String {
+    format!(
+        "{} {} -> {}",
+        field.field,
+        value_text(&field.before),
+        value_text(&field.after)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Role;
+    use crate::costing::CostingMethod;
+    use crate::currency::Conversion;
+    use crate::money::Money;
+    use crate::output::Output;
+    use crate::preview;
+    use crate::sales::Sales;
+    use crate::shell::parse_line;
+    use crate::units::Unit;
+
+    #[test]
+    fn test_store_diff() -> Result<(), StoreError> {
+        let mut before = Store::default();
+        before.inventory.add_item("Orange", Money::from_cents(50), Money::from_cents(75), 10.into(), Unit::Each, "Fruit")?;
+        before.inventory.add_item("Apple", Money::from_cents(40), Money::from_cents(60), 5.into(), Unit::Each, "Fruit")?;
+        let mut after = before.clone();
+        after.sales.record_sale("Orange", 4.into(), None, None, &Conversion::base("USD"), CostingMethod::Fifo, &mut after.inventory)?;
+        after.inventory.purge_item("Apple", &[], &[])?;
+        after.inventory.add_item("Kiwi", Money::from_cents(20), Money::from_cents(40), 3.into(), Unit::Each, "Fruit")?;
+
+        let diff = StoreDiff::between(&before, &after)?;
+        assert_eq!(diff.sales.len(), 1);
+        assert!(diff.purchases.is_empty() && diff.settings.is_empty());
+        let text = diff.generate_text();
+        assert!(text.contains("  ~ Orange: quantity 10 -> 6\n"));
+        assert!(text.contains("  - Apple\n") && text.contains("  + Kiwi\n"));
+        assert!(text.contains("Sales:\n  + #1 Orange\n"));
+        assert!(StoreDiff::between(&before, &before)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run_json_is_one_document() -> Result<(), StoreError> {
+        let mut store = Store::default();
+        store.inventory.add_item("Orange", Money::from_cents(50), Money::from_cents(75), 10.into(), Unit::Each, "Fruit")?;
+        let cli = parse_line("update-product -n Orange -q +2 --output json --dry-run", Output::default()).unwrap();
+        let (result, captured) = Output::capture(|| preview(cli.command, &store, Role::Manager, Output::new(cli.output)));
+        result?;
+        let stdout: String = captured.values.iter().map(|value| format!("{value:#}\n")).collect::<String>() + &captured.text;
+        let json: Value = serde_json::from_str(&stdout)?;
+        assert_eq!(json["result"]["quantity"], "12");
+        assert_eq!(json["dry_run"]["inventory"][0]["record"], "Orange");
+        assert_eq!(store.inventory.get_item("Orange").unwrap().quantity, 10.into());
+        Ok(())
+    }
+}
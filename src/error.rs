@@ -0,0 +1,360 @@
+use crate::units::{Quantity, Unit};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use validator::ValidationErrors;
+
+// The kinds of record an operation can fail to find or find already taken.
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entity {
+    Product,
+    Variant,
+    Sku,
+    Pack,
+    Category,
+    Attribute,
+    Tag,
+    ExchangeRate,
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entity = match self {
+            Entity::Product => "Product",
+            Entity::Variant => "Variant",
+            Entity::Sku => "SKU",
+            Entity::Pack => "Pack",
+            Entity::Category => "Category",
+            Entity::Attribute => "Attribute",
+            Entity::Tag => "Tag",
+            Entity::ExchangeRate => "Exchange rate",
+        };
+        write!(f, "{entity}")
+    }
+}
+
+// A field of a record that failed validation, with the reason.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreError {
+    // Login failed, or the user's role does not allow the operation.
+    Unauthorized(String),
+    // `product` is set for things a product holds, such as its packs and tags. Product
+    // lookups carry the names of products with similar names.
+    NotFound {
+        entity: Entity,
+        name: String,
+        product: Option<String>,
+        suggestions: Vec<String>,
+    },
+    AlreadyExists {
+        entity: Entity,
+        name: String,
+    },
+    // Quantities are in the product's unit.
+    InsufficientStock {
+        product: String,
+        requested: Quantity,
+        available: Quantity,
+        unit: Unit,
+    },
+    Validation(Vec<FieldError>),
+    // A request the store's rules do not allow in its current state, such as selling an
+    // archived product.
+    Invalid(String),
+    // Reading or writing the data files failed.
+    Storage(String),
+    // A line of a script failed, so none of the script's changes were kept. The exit code
+    // and kind are those of the line's error.
+    Script {
+        line: usize,
+        command: String,
+        error: Box<StoreError>,
+    },
+}
+
+impl StoreError {
+    // Process exit code for each class of error. Codes start at 3, as clap already exits
+    // with 2 for a bad command line.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            StoreError::Unauthorized(_) => 3,
+            StoreError::NotFound { .. } => 4,
+            StoreError::AlreadyExists { .. }
+            | StoreError::Validation(_)
+            | StoreError::Invalid(_) => 5,
+            StoreError::InsufficientStock { .. } => 6,
+            StoreError::Storage(_) => 7,
+            StoreError::Script { error, .. } => error.exit_code(),
+        }
+    }
+
+    // Stable name of the error's class, used in JSON output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StoreError::Unauthorized(_) => "unauthorized",
+            StoreError::NotFound { .. } => "not_found",
+            StoreError::AlreadyExists { .. } => "already_exists",
+            StoreError::InsufficientStock { .. } => "insufficient_stock",
+            StoreError::Validation(_) => "validation",
+            StoreError::Invalid(_) => "invalid",
+            StoreError::Storage(_) => "storage",
+            StoreError::Script { error, .. } => error.kind(),
+        }
+    }
+
+    pub fn not_found(entity: Entity, name: &str) -> Self {
+        StoreError::NotFound {
+            entity,
+            name: name.to_string(),
+            product: None,
+            suggestions: Vec::new(),
+        }
+    }
+
+    // Something the named product does not have, e.g. a pack or a tag.
+    pub fn not_on_product(product: &str, entity: Entity, name: &str) -> Self {
+        StoreError::NotFound {
+            entity,
+            name: name.to_string(),
+            product: Some(product.to_string()),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn already_exists(entity: Entity, name: &str) -> Self {
+        StoreError::AlreadyExists {
+            entity,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn invalid_field(field: &str, message: impl Into<String>) -> Self {
+        StoreError::Validation(vec![FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }])
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        StoreError::Invalid(message.into())
+    }
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Unauthorized(message)
+            | StoreError::Invalid(message)
+            | StoreError::Storage(message) => write!(f, "{message}"),
+            StoreError::NotFound {
+                entity,
+                name,
+                product: Some(product),
+                ..
+            } => write!(
+                f,
+                "Product {product} has no {} {name}",
+                entity.to_string().to_lowercase()
+            ),
+            StoreError::NotFound {
+                entity,
+                name,
+                suggestions,
+                ..
+            } => {
+                write!(f, "{entity} {name} not found")?;
+                if !suggestions.is_empty() {
+                    write!(f, ". Did you mean {}?", suggestions.join(", "))?;
+                }
+                Ok(())
+            }
+            StoreError::AlreadyExists { entity, name } => {
+                write!(f, "{entity} {name} already exists")
+            }
+            StoreError::InsufficientStock {
+                product,
+                requested,
+                available,
+                unit,
+            } => write!(
+                f,
+                "Insufficient stock for '{product}'. Available: {available} {unit}, Requested: {requested} {unit}"
+            ),
+            StoreError::Validation(fields) => {
+                let messages: Vec<&str> = fields.iter().map(|e| e.message.as_str()).collect();
+                write!(f, "Validation failed: {}", messages.join("; "))
+            }
+            StoreError::Script {
+                line,
+                command,
+                error,
+            } => write!(
+                f,
+                "Script rolled back at line {line} ({command}): {error}; nothing was changed"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+// An object with the error's kind, message and exit code, plus the fields of its variant,
+// e.g. {"kind": "insufficient_stock", "message": "...", "exit_code": 6, "product": "Cola",
+// "requested": "5", "available": "2", "unit": "each"}. A script error has the fields of
+// the line's error, plus "line" and "command".
+impl Serialize for StoreError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        map.serialize_entry("exit_code", &self.exit_code())?;
+        self.serialize_fields(&mut map)?;
+        map.end()
+    }
+}
+
+impl StoreError {
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        match self {
+            StoreError::NotFound {
+                entity,
+                name,
+                product,
+                suggestions,
+            } => {
+                map.serialize_entry("entity", entity)?;
+                map.serialize_entry("name", name)?;
+                map.serialize_entry("product", product)?;
+                map.serialize_entry("suggestions", suggestions)?;
+            }
+            StoreError::AlreadyExists { entity, name } => {
+                map.serialize_entry("entity", entity)?;
+                map.serialize_entry("name", name)?;
+            }
+            StoreError::InsufficientStock {
+                product,
+                requested,
+                available,
+                unit,
+            } => {
+                map.serialize_entry("product", product)?;
+                map.serialize_entry("requested", requested)?;
+                map.serialize_entry("available", available)?;
+                map.serialize_entry("unit", unit)?;
+            }
+            StoreError::Validation(fields) => map.serialize_entry("fields", fields)?,
+            StoreError::Script {
+                line,
+                command,
+                error,
+            } => {
+                error.serialize_fields(map)?;
+                map.serialize_entry("line", line)?;
+                map.serialize_entry("command", command)?;
+            }
+            StoreError::Unauthorized(_) | StoreError::Invalid(_) | StoreError::Storage(_) => {}
+        }
+        Ok(())
+    }
+}
+
+// One field error per failed rule, in field order.
+impl From<ValidationErrors> for StoreError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields: Vec<FieldError> = errors
+            .field_errors()
+            .into_iter()
+            .flat_map(|(field, errors)| {
+                errors.iter().map(move |error| FieldError {
+                    field: field.to_string(),
+                    message: error
+                        .message
+                        .as_ref()
+                        .map_or_else(|| format!("Invalid {field}"), |m| m.to_string()),
+                })
+            })
+            .collect();
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+        StoreError::Validation(fields)
+    }
+}
+
+impl From<std::io::Error> for StoreError {
+    fn from(error: std::io::Error) -> Self {
+        StoreError::Storage(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(error: serde_json::Error) -> Self {
+        StoreError::Storage(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::{Product, ProductStatus};
+    use crate::money::Money;
+    use std::collections::BTreeMap;
+    use validator::Validate;
+
+    #[test]
+    fn test_validation_errors_by_field() {
+        let product = Product {
+            name: String::new(),
+            cost_price: Money::from_cents(100),
+            retail_price: Money::from_cents(200),
+            quantity: 1.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
+            description: String::new(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
+        };
+        let error = StoreError::from(product.validate().unwrap_err());
+        let fields: Vec<&str> = match &error {
+            StoreError::Validation(fields) => fields.iter().map(|e| e.field.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        assert_eq!(fields, vec!["description", "name"]);
+        assert_eq!(error.to_string(), "Validation failed: Description must be 1-255 characters; Product name must be 1-50 characters");
+    }
+
+    #[test]
+    fn test_exit_codes_by_class() {
+        let stock = StoreError::InsufficientStock { product: "Cola".to_string(), requested: 2.into(), available: 1.into(), unit: Unit::Each };
+        let codes: Vec<u8> = [StoreError::Unauthorized(String::new()), StoreError::not_found(Entity::Product, "Cola"), StoreError::invalid_field("name", "Too long"), StoreError::already_exists(Entity::Product, "Cola"), stock, StoreError::Storage(String::new())].iter().map(StoreError::exit_code).collect();
+        assert_eq!(codes, vec![3, 4, 5, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_not_found_messages() {
+        assert_eq!(StoreError::not_found(Entity::Category, "Dairy").to_string(), "Category Dairy not found");
+        assert_eq!(StoreError::not_on_product("Cola", Entity::Pack, "case").to_string(), "Product Cola has no pack case");
+        assert_eq!(StoreError::already_exists(Entity::Sku, "TS-M").to_string(), "SKU TS-M already exists");
+    }
+
+    #[test]
+    fn test_error_json() {
+        let stock = StoreError::InsufficientStock { product: "Cola".to_string(), requested: 5.into(), available: 2.into(), unit: Unit::Each };
+        let json = serde_json::to_value(&stock).unwrap();
+        assert_eq!(json["kind"], "insufficient_stock");
+        assert_eq!(json["exit_code"], 6);
+        assert_eq!((json["requested"].as_str(), json["available"].as_str()), (Some("5"), Some("2")));
+        let json = serde_json::to_value(StoreError::invalid_field("name", "Too long")).unwrap();
+        assert_eq!(json["fields"][0]["field"], "name");
+        assert_eq!(json["message"], "Validation failed: Too long");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::inventory::{Product, ProductStatus};
+use crate::money::Money;
+use crate::units::Quantity;
+use clap::ValueEnum;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn accepts(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+// A condition on a product attribute such as `organic=true` or `weight>=500`. The value
+// is parsed with the type of the product's own attribute when the filter is applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeFilter {
+    pub name: String,
+    pub comparison: Comparison,
+    pub value: String,
+}
+
+pub fn parse_attribute_filter(filter: &str) -> Result<AttributeFilter, String> {
+    const OPERATORS: [(&str, Comparison); 6] = [
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("=", Comparison::Equal),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    let start = filter
+        .find(['=', '!', '<', '>'])
+        .ok_or_else(|| format!("Invalid attribute filter {filter}, expected e.g. name=value"))?;
+    let (name, rest) = filter.split_at(start);
+    let (operator, comparison) = OPERATORS
+        .iter()
+        .find(|(operator, _)| rest.starts_with(operator))
+        .ok_or_else(|| format!("Invalid attribute filter {filter}"))?;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(format!(
+            "Invalid attribute filter {filter}, missing attribute name"
+        ));
+    }
+
+    Ok(AttributeFilter {
+        name: name.to_string(),
+        comparison: *comparison,
+        value: rest[operator.len()..].trim().to_string(),
+    })
+}
+
+// Selects products for listings and reports. A product must meet every condition that is
+// set: its name contains `name` (ignoring case), its retail price and quantity lie within
+// the inclusive ranges, its category is one of `categories`, it carries every tag and it
+// satisfies every attribute condition. An empty filter selects everything.
+#[derive(Debug, Clone, Default)]
+pub struct ProductFilter {
+    pub name: Option<String>,
+    pub min_price: Option<Money>,
+    pub max_price: Option<Money>,
+    pub min_quantity: Option<Quantity>,
+    pub max_quantity: Option<Quantity>,
+    pub categories: Vec<String>,
+    pub status: Option<ProductStatus>,
+    pub tags: Vec<String>,
+    pub attributes: Vec<AttributeFilter>,
+}
+
+impl ProductFilter {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.min_price.is_none()
+            && self.max_price.is_none()
+            && self.min_quantity.is_none()
+            && self.max_quantity.is_none()
+            && self.categories.is_empty()
+            && self.status.is_none()
+            && self.tags.is_empty()
+            && self.attributes.is_empty()
+    }
+
+    pub fn matches(&self, product: &Product) -> bool {
+        self.name.as_ref().is_none_or(|name| {
+            product
+                .name
+                .to_lowercase()
+                .contains(&name.trim().to_lowercase())
+        }) && self.min_price.is_none_or(|min| product.retail_price >= min)
+            && self.max_price.is_none_or(|max| product.retail_price <= max)
+            && self.min_quantity.is_none_or(|min| product.quantity >= min)
+            && self.max_quantity.is_none_or(|max| product.quantity <= max)
+            && (self.categories.is_empty()
+                || product
+                    .category
+                    .as_ref()
+                    .is_some_and(|c| self.categories.contains(c)))
+            && self.status.is_none_or(|status| product.status == status)
+            && self.tags.iter().all(|tag| product.has_tag(tag))
+            && self.attributes.iter().all(|filter| {
+                product.attributes.get(&filter.name).is_some_and(|value| {
+                    value
+                        .kind()
+                        .parse(&filter.value)
+                        .ok()
+                        .and_then(|expected| value.partial_cmp(&expected))
+                        .is_some_and(|ordering| filter.comparison.accepts(ordering))
+                })
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortField {
+    Name,
+    CostPrice,
+    RetailPrice,
+    Quantity,
+    Unit,
+    Category,
+    Status,
+    StockValue,
+}
+
+impl SortField {
+    // Orders two products by this field, falling back to the name so that listings are
+    // stable between runs.
+    pub fn compare(&self, a: &Product, b: &Product) -> Ordering {
+        let ordering = match self {
+            SortField::Name => Ordering::Equal,
+            SortField::CostPrice => a.cost_price.cmp(&b.cost_price),
+            SortField::RetailPrice => a.retail_price.cmp(&b.retail_price),
+            SortField::Quantity => a.quantity.cmp(&b.quantity),
+            SortField::Unit => a.unit.to_string().cmp(&b.unit.to_string()),
+            SortField::Category => a.category.cmp(&b.category),
+            SortField::Status => a.status.to_string().cmp(&b.status.to_string()),
+            SortField::StockValue => a.stock_value().cmp(&b.stock_value()),
+        };
+        ordering.then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+    }
+}
+
+// One page of a listing, counting pages from 1. A page past the end is empty.
+pub fn page<T>(items: &[T], page: usize, per_page: usize) -> &[T] {
+    let start = page
+        .saturating_sub(1)
+        .saturating_mul(per_page)
+        .min(items.len());
+    let end = start.saturating_add(per_page).min(items.len());
+    &items[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StoreError;
+    use crate::inventory::Inventory;
+    use crate::units::Unit;
+
+    #[test]
+    fn test_parse_attribute_filter() {
+        let filter = parse_attribute_filter("weight>=500").unwrap();
+        assert_eq!(filter.name, "weight");
+        assert_eq!(filter.comparison, Comparison::GreaterOrEqual);
+        assert_eq!(filter.value, "500");
+        assert_eq!(parse_attribute_filter("brand!=Acme").unwrap().comparison, Comparison::NotEqual);
+        assert!(parse_attribute_filter("brand").is_err());
+        assert!(parse_attribute_filter("=Acme").is_err());
+    }
+
+    #[test]
+    fn test_filter_sort_and_page() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Orange Juice", Money::from_cents(150), Money::from_cents(300), 10.into(), Unit::Each, "Juice")?;
+        inventory.add_item("Orange", Money::from_cents(20), Money::from_cents(50), 100.into(), Unit::Each, "Fruit")?;
+        inventory.add_item("Apple", Money::from_cents(25), Money::from_cents(60), 5.into(), Unit::Each, "Fruit")?;
+
+        let filter = ProductFilter {
+            name: Some("orange".to_string()),
+            max_price: Some(Money::from_cents(100)),
+            ..Default::default()
+        };
+        let names: Vec<&str> = inventory.iter().filter(|p| filter.matches(p)).map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Orange"]);
+
+        let mut products: Vec<&Product> = inventory.iter().collect();
+        products.sort_by(|a, b| SortField::Quantity.compare(a, b));
+        let names: Vec<&str> = products.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Apple", "Orange Juice", "Orange"]);
+        assert_eq!(page(&products, 2, 2).len(), 1);
+        assert!(page(&products, 3, 2).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use crate::inventory::{Inventory, Product, name_key};
+use crate::search::SearchIndex;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+
+// An inventory that finds products through hash indexes instead of scanning the list, for
+// catalogues with many thousands of products. Products keep their order, so reports and
+// saved data look the same as with a plain list.
+#[derive(Debug, Clone, Default)]
+pub struct IndexedInventory {
+    products: Vec<Product>,
+    // Name key of every name, alias and SKU, to the positions of the products using it.
+    // Legacy data can have two products with the same key; the first one wins, as in a
+    // plain list.
+    keys: HashMap<String, BTreeSet<usize>>,
+    // Parent product name to the positions of its variants.
+    variants: HashMap<String, BTreeSet<usize>>,
+    categories: HashMap<String, BTreeSet<usize>>,
+    tags: HashMap<String, BTreeSet<usize>>,
+    // Kept up to date like the other indexes, so searches and suggestions do not index
+    // every product again.
+    search: SearchIndex,
+}
+
+impl IndexedInventory {
+    // Products filed directly under a category, in inventory order.
+    pub fn in_category(&self, category: &str) -> Vec<&Product> {
+        self.lookup(&self.categories, category)
+    }
+
+    pub fn tagged(&self, tag: &str) -> Vec<&Product> {
+        self.lookup(&self.tags, &tag.trim().to_lowercase())
+    }
+
+    fn lookup(&self, index: &HashMap<String, BTreeSet<usize>>, key: &str) -> Vec<&Product> {
+        index
+            .get(key)
+            .map(|positions| positions.iter().map(|&i| &self.products[i]).collect())
+            .unwrap_or_default()
+    }
+
+    fn keys_of(product: &Product) -> impl Iterator<Item = String> + '_ {
+        std::iter::once(&product.name)
+            .chain(&product.aliases)
+            .chain(product.variant.as_ref().map(|v| &v.sku))
+            .map(|name| name_key(name))
+    }
+
+    fn index(&mut self, position: usize) {
+        let product = &self.products[position];
+        for key in Self::keys_of(product) {
+            self.keys.entry(key).or_default().insert(position);
+        }
+        if let Some(variant) = &product.variant {
+            self.variants
+                .entry(variant.parent.clone())
+                .or_default()
+                .insert(position);
+        }
+        if let Some(category) = &product.category {
+            self.categories
+                .entry(category.clone())
+                .or_default()
+                .insert(position);
+        }
+        for tag in &product.tags {
+            self.tags.entry(tag.clone()).or_default().insert(position);
+        }
+        self.search.set(position, product);
+    }
+
+    fn unindex(&mut self, position: usize) {
+        fn remove(index: &mut HashMap<String, BTreeSet<usize>>, key: &str, position: usize) {
+            if let Some(positions) = index.get_mut(key) {
+                positions.remove(&position);
+                if positions.is_empty() {
+                    index.remove(key);
+                }
+            }
+        }
+
+        let product = &self.products[position];
+        for key in Self::keys_of(product) {
+            remove(&mut self.keys, &key, position);
+        }
+        if let Some(variant) = &product.variant {
+            remove(&mut self.variants, &variant.parent, position);
+        }
+        if let Some(category) = &product.category {
+            remove(&mut self.categories, category, position);
+        }
+        for tag in &product.tags {
+            remove(&mut self.tags, tag, position);
+        }
+    }
+
+    fn rebuild(&mut self) {
+        self.keys.clear();
+        self.variants.clear();
+        self.categories.clear();
+        self.tags.clear();
+        self.search = SearchIndex::default();
+        (0..self.products.len()).for_each(|position| self.index(position));
+    }
+}
+
+impl From<Vec<Product>> for IndexedInventory {
+    fn from(products: Vec<Product>) -> Self {
+        let mut inventory = IndexedInventory {
+            products,
+            ..Default::default()
+        };
+        inventory.rebuild();
+        inventory
+    }
+}
+
+impl Inventory for IndexedInventory {
+    fn products(&self) -> &[Product] {
+        &self.products
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.keys
+            .get(&name_key(name))
+            .and_then(|positions| positions.first().copied())
+    }
+
+    fn variant_positions(&self, parent: &str) -> Vec<usize> {
+        self.variants
+            .get(parent)
+            .map(|positions| positions.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn insert_product(&mut self, product: Product) {
+        self.products.push(product);
+        self.index(self.products.len() - 1);
+    }
+
+    fn update_product<R>(&mut self, index: usize, update: impl FnOnce(&mut Product) -> R) -> R {
+        self.unindex(index);
+        let result = update(&mut self.products[index]);
+        self.index(index);
+        result
+    }
+
+    // Removing a product shifts the products after it, so the indexes are rebuilt.
+    // Products are rarely removed, unlike the lookups the indexes serve.
+    fn take_product(&mut self, index: usize) -> Product {
+        let product = self.products.remove(index);
+        self.rebuild();
+        product
+    }
+
+    fn search_index(&self) -> Cow<'_, SearchIndex> {
+        Cow::Borrowed(&self.search)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::StoreError;
+    use crate::category::{Categories, Category};
+    use crate::money::Money;
+    use crate::search::product_not_found;
+    use crate::units::Unit;
+
+    #[test]
+    fn test_indexed_lookup() -> Result<(), StoreError> {
+        let mut inventory = IndexedInventory::default();
+        inventory.add_item("T-Shirt", Money::from_cents(600), Money::from_cents(1500), 0.into(), Unit::Each, "Cotton T-shirt")?;
+        inventory.add_item("Orange", Money::from_cents(20), Money::from_cents(50), 100.into(), Unit::Each, "Fruit")?;
+        inventory.add_variant("T-Shirt", "TS-M", [("size".to_string(), "M".to_string())].into(), 5.into(), None, None)?;
+        assert!(inventory.add_item("orange ", Money::from_cents(20), Money::from_cents(50), 1.into(), Unit::Each, "Fruit").is_err());
+        assert_eq!(inventory.get_item("ts-m").unwrap().name, "T-Shirt (M)");
+
+        inventory.rename_item("T-Shirt", "Tee")?;
+        assert_eq!(inventory.get_item("T-Shirt").unwrap().name, "Tee");
+        assert_eq!(inventory.variants_of("Tee").len(), 1);
+        assert!(inventory.variants_of("T-Shirt").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexed_search() -> Result<(), StoreError> {
+        let mut inventory = IndexedInventory::default();
+        inventory.add_item("Orange", Money::from_cents(20), Money::from_cents(50), 10.into(), Unit::Each, "Fruit")?;
+        inventory.add_item("Apple", Money::from_cents(20), Money::from_cents(50), 10.into(), Unit::Each, "Fruit")?;
+        inventory.add_item("Lemon", Money::from_cents(20), Money::from_cents(50), 10.into(), Unit::Each, "Fruit")?;
+        let names = |inventory: &IndexedInventory, query: &str| inventory.search_index().search(query).into_iter().map(|hit| hit.name).collect::<Vec<_>>();
+        inventory.rename_item("Orange", "Blood Orange")?;
+        inventory.add_tag("Lemon", "citrus")?;
+        assert_eq!(names(&inventory, "orange"), vec!["Blood Orange"]);
+        assert_eq!(names(&inventory, "citrus"), vec!["Lemon"]);
+
+        inventory.purge_item("Apple", &[], &[])?;
+        inventory.remove_item("Blood Orange")?;
+        assert!(names(&inventory, "orange").is_empty());
+        assert_eq!(names(&inventory, "fruit"), vec!["Lemon"]);
+        assert_eq!(product_not_found("lemn", &inventory).to_string(), "Product lemn not found. Did you mean Lemon?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_indexed_categories_and_tags() -> Result<(), StoreError> {
+        let mut categories: Vec<Category> = Vec::new();
+        categories.add_category("Produce", None)?;
+        let mut inventory = IndexedInventory::default();
+        inventory.add_item("Apple", Money::from_cents(20), Money::from_cents(50), 10.into(), Unit::Each, "Fruit")?;
+        inventory.add_item("Orange", Money::from_cents(20), Money::from_cents(50), 10.into(), Unit::Each, "Fruit")?;
+        inventory.set_category("Orange", Some("Produce"), &categories)?;
+        inventory.add_tag("Orange", "Citrus")?;
+        inventory.add_tag("Apple", "citrus")?;
+        inventory.remove_tag("Apple", "citrus")?;
+        assert_eq!(inventory.in_category("Produce").len(), 1);
+        assert_eq!(inventory.tagged("CITRUS")[0].name, "Orange");
+
+        inventory.purge_item("Apple", &[], &[])?;
+        assert_eq!(inventory.get_item("Orange").unwrap().category.as_deref(), Some("Produce"));
+        assert_eq!(inventory.in_category("Produce")[0].name, "Orange");
+        Ok(())
+    }
+}
@@ -1,157 +1,851 @@
+use crate::attributes::{AttributeDefinition, AttributeValue};
+use crate::category::{Category, find_category};
+use crate::costing::{CostLayer, CostingMethod, layers_value, reconcile_layers};
+use crate::error::{Entity, StoreError};
+use crate::money::{Money, validate_non_negative};
+use crate::purchase::Purchase;
+use crate::sales::Sale;
+use crate::search::{SearchIndex, product_not_found};
+use crate::units::{Pack, Quantity, Unit, pack_size, validate_non_negative_quantity};
+use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
 use validator::Validate;
 
-#[derive(Validate, Debug, Clone, Serialize, Deserialize,PartialEq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ProductStatus {
+    #[default]
+    Active,
+    Discontinued,
+    Archived,
+    Seasonal,
+}
+
+impl fmt::Display for ProductStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ProductStatus::Active => "active",
+            ProductStatus::Discontinued => "discontinued",
+            ProductStatus::Archived => "archived",
+            ProductStatus::Seasonal => "seasonal",
+        };
+        write!(f, "{status}")
+    }
+}
+
+// Marks a product as a variant of a parent product, e.g. size M of a T-shirt. A variant
+// keeps its own stock and history; its retail price follows the parent's unless it has
+// a price override.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Variant {
+    pub parent: String,
+    pub sku: String,
+    pub options: BTreeMap<String, String>,
+    #[serde(default)]
+    pub price_override: Option<Money>,
+}
+
+pub fn parse_variant_option(option: &str) -> Result<(String, String), String> {
+    match option.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid variant option {option}, expected key=value"
+        )),
+    }
+}
+
+// A change to a product's stock in update-product: a new stock level, or an adjustment
+// written with a sign, e.g. "+5" or "-3".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuantityChange {
+    Set(Quantity),
+    Add(Quantity),
+    Remove(Quantity),
+}
+
+impl QuantityChange {
+    // The stock after the change. Removing more than the product has is refused, as
+    // for a sale.
+    fn apply(self, product: &Product) -> Result<Quantity, StoreError> {
+        match self {
+            QuantityChange::Set(quantity) => Ok(quantity),
+            QuantityChange::Add(quantity) => Ok(product.quantity + quantity),
+            QuantityChange::Remove(quantity) if quantity > product.quantity => {
+                Err(StoreError::InsufficientStock {
+                    product: product.name.clone(),
+                    requested: quantity,
+                    available: product.quantity,
+                    unit: product.unit,
+                })
+            }
+            QuantityChange::Remove(quantity) => Ok(product.quantity - quantity),
+        }
+    }
+
+    fn amount(self) -> Quantity {
+        match self {
+            QuantityChange::Set(quantity)
+            | QuantityChange::Add(quantity)
+            | QuantityChange::Remove(quantity) => quantity,
+        }
+    }
+}
+
+impl FromStr for QuantityChange {
+    type Err = String;
+
+    // One leading sign at most: the amount after it is unsigned, so "--5" and "+-5" are
+    // rejected rather than turned around.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (change, amount): (fn(Quantity) -> Self, &str) =
+            if let Some(amount) = s.strip_prefix('+') {
+                (QuantityChange::Add, amount)
+            } else if let Some(amount) = s.strip_prefix('-') {
+                (QuantityChange::Remove, amount)
+            } else {
+                (QuantityChange::Set, s)
+            };
+        if amount.trim_start().starts_with(['+', '-']) {
+            return Err(format!("Invalid quantity: {s}"));
+        }
+        Ok(change(amount.parse()?))
+    }
+}
+
+// The changes update_item makes to a product. Fields left as None keep their value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProductUpdate {
+    pub cost_price: Option<Money>,
+    pub retail_price: Option<Money>,
+    pub quantity: Option<QuantityChange>,
+    pub description: Option<String>,
+}
+
+impl ProductUpdate {
+    pub fn is_empty(&self) -> bool {
+        *self == ProductUpdate::default()
+    }
+}
+
+// Names are stored as entered apart from surrounding spaces and Unicode form (NFC), and
+// compared by their key, which also ignores case: "Orange", "orange" and "Orange " all
+// name the same product.
+pub fn display_name(name: &str) -> String {
+    name.trim().nfc().collect()
+}
+
+pub fn name_key(name: &str) -> String {
+    display_name(name).to_lowercase()
+}
+
+// Position of the product called `name`; when there is none, the error suggests products
+// with similar names.
+fn position_of<I: Inventory + ?Sized>(inventory: &I, name: &str) -> Result<usize, StoreError> {
+    inventory
+        .position(name)
+        .ok_or_else(|| product_not_found(name, inventory))
+}
+
+// Tags are free-form labels compared without regard to case or surrounding spaces.
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+#[derive(Validate, Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Product {
     #[validate(length(min = 1, max = 50, message = "Product name must be 1-50 characters"))]
     pub name: String,
-    #[validate(range(min = 0.0, message = "Price must be greater than 0"))]
-    pub price: f64,
-    #[validate(range(min = 0, message = "Quantity must be greater than 0"))]
-    pub quantity: u32,
+    #[validate(custom(
+        function = "validate_non_negative",
+        message = "Cost price must be greater than 0"
+    ))]
+    pub cost_price: Money,
+    #[validate(custom(
+        function = "validate_non_negative",
+        message = "Retail price must be greater than 0"
+    ))]
+    pub retail_price: Money,
+    #[validate(custom(
+        function = "validate_non_negative_quantity",
+        message = "Quantity must be greater than 0"
+    ))]
+    pub quantity: Quantity,
+    #[serde(default)]
+    pub unit: Unit,
+    #[serde(default)]
+    pub packs: Vec<Pack>,
     #[validate(length(min = 1, max = 255, message = "Description must be 1-255 characters"))]
     pub description: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub status: ProductStatus,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, AttributeValue>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub variant: Option<Variant>,
+    #[serde(default)]
+    pub cost_layers: Vec<CostLayer>,
 }
 
-pub trait Inventory {
-    fn add_item(
-        &mut self,
-        name: &str,
-        price: f64,
-        quantity: u32,
-        description: &str,
-    ) -> Result<(), String>;
-    fn remove_item(&mut self, name: &str) -> Result<(), String>;
-    fn update_item(
-        &mut self,
-        name: &str,
-        price: f64,
-        quantity: u32,
-        description: &str,
-    ) -> Result<(), String>;
-    fn get_item(&self, name: &str) -> Option<&Product>;
+impl Product {
+    pub fn matches(&self, name: &str) -> bool {
+        let key = name_key(name);
+        name_key(&self.name) == key
+            || self.aliases.iter().any(|alias| name_key(alias) == key)
+            || self
+                .variant
+                .as_ref()
+                .is_some_and(|v| name_key(&v.sku) == key)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.contains(&normalize_tag(tag))
+    }
+
+    pub fn is_variant_of(&self, parent: &str) -> bool {
+        self.variant.as_ref().is_some_and(|v| v.parent == parent)
+    }
+
+    pub fn is_archived(&self) -> bool {
+        self.status == ProductStatus::Archived
+    }
+
+    pub fn stock_value(&self) -> Money {
+        layers_value(&self.cost_layers)
+    }
+
+    pub fn pack_size(&self, pack: &str) -> Result<Quantity, StoreError> {
+        pack_size(&self.packs, pack)
+    }
+
+    pub fn reconcile_cost_layers(&mut self, method: CostingMethod) {
+        reconcile_layers(
+            &mut self.cost_layers,
+            self.quantity,
+            self.cost_price,
+            method,
+        );
+    }
 }
 
-impl Inventory for Vec<Product> {
+// Operations on a store's products. Implementations only provide the storage methods at
+// the top, which decide how products are found; the operations are built on those.
+// Every change to a stored product goes through `update_product`, so an implementation
+// can keep any indexes it has up to date.
+pub trait Inventory {
+    fn products(&self) -> &[Product];
+    // Position of the product with this name, alias or SKU.
+    fn position(&self, name: &str) -> Option<usize>;
+    fn variant_positions(&self, parent: &str) -> Vec<usize>;
+    fn insert_product(&mut self, product: Product);
+    fn update_product<R>(&mut self, index: usize, update: impl FnOnce(&mut Product) -> R) -> R;
+    fn take_product(&mut self, index: usize) -> Product;
+    fn search_index(&self) -> Cow<'_, SearchIndex>;
+
     fn add_item(
         &mut self,
         name: &str,
-        price: f64,
-        quantity: u32,
+        cost_price: Money,
+        retail_price: Money,
+        quantity: Quantity,
+        unit: Unit,
         description: &str,
-    ) -> Result<(), String> {
-        let product = Product {
+    ) -> Result<(), StoreError> {
+        quantity.check_precision(unit)?;
+        let name = &display_name(name);
+        let mut product = Product {
             name: name.to_string(),
-            price,
+            cost_price,
+            retail_price,
             quantity,
+            unit,
+            packs: Vec::new(),
             description: description.to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
         };
+        // The costing method only decides which layers stock is removed from, and a new
+        // product has none yet.
+        product.reconcile_cost_layers(CostingMethod::default());
 
-        if self.iter().any(|p| p.name == name) {
-            return Err(format!("Product {name} already exists"));
+        if self.position(name).is_some() {
+            return Err(StoreError::already_exists(Entity::Product, name));
         };
 
-        product
-            .validate()
-            .map_err(|errors| format!("Validation errors: {errors:#?}"))?;
+        product.validate()?;
 
-        self.push(product);
+        self.insert_product(product);
         Ok(())
     }
 
-    fn remove_item(&mut self, name: &str) -> Result<(), String> {
-        match self.iter().position(|p| p.name == name) {
-            Some(index) => {
-                self.remove(index);
-                Ok(())
-            }
-            None => Err(format!("Product {name} not found")),
+    fn remove_item(&mut self, name: &str) -> Result<(), StoreError> {
+        self.set_status(name, ProductStatus::Archived)
+    }
+
+    fn set_status(&mut self, name: &str, status: ProductStatus) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+        self.update_product(index, |product| product.status = status);
+        Ok(())
+    }
+
+    fn set_category(
+        &mut self,
+        name: &str,
+        category: Option<&str>,
+        categories: &[Category],
+    ) -> Result<(), StoreError> {
+        let category = category
+            .map(|category| {
+                find_category(categories, category)
+                    .map(|c| c.name.as_str())
+                    .ok_or_else(|| StoreError::not_found(Entity::Category, category))
+            })
+            .transpose()?;
+        let index = position_of(self, name)?;
+        // Variants are always filed with their parent.
+        let name = self.products()[index].name.clone();
+        for index in std::iter::once(index).chain(self.variant_positions(&name)) {
+            self.update_product(index, |p| p.category = category.map(str::to_string));
         }
+        Ok(())
     }
 
+    fn purge_item(
+        &mut self,
+        name: &str,
+        sales: &[Sale],
+        purchases: &[Purchase],
+    ) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+
+        let product = &self.products()[index];
+        if !self.variant_positions(&product.name).is_empty() {
+            return Err(StoreError::invalid(format!(
+                "Product {} has variants and cannot be purged",
+                product.name
+            )));
+        }
+        if sales.iter().any(|s| product.matches(&s.product_name))
+            || purchases.iter().any(|p| product.matches(&p.product_name))
+        {
+            return Err(StoreError::invalid(format!(
+                "Product {} has sales or purchase history and cannot be purged",
+                product.name
+            )));
+        }
+
+        self.take_product(index);
+        Ok(())
+    }
+
+    // Applies only the changes given in `update`, so that e.g. fixing a description does
+    // not overwrite stock recorded in the meantime.
+    // Stock removed by lowering the quantity comes off the cost layers `costing_method`
+    // would sell first.
     fn update_item(
         &mut self,
         name: &str,
-        price: f64,
-        quantity: u32,
-        description: &str,
-    ) -> Result<(), String> {
-        let temp_product = Product {
-            name: name.to_string(),
-            price,
+        update: ProductUpdate,
+        costing_method: CostingMethod,
+    ) -> Result<(), StoreError> {
+        if update.is_empty() {
+            return Err(StoreError::invalid(
+                "Nothing to update; give a cost price, retail price, quantity or description",
+            ));
+        }
+        let index = position_of(self, name)?;
+        let product = &self.products()[index];
+        let mut updated = product.clone();
+        if let Some(cost_price) = update.cost_price {
+            updated.cost_price = cost_price;
+        }
+        if let Some(retail_price) = update.retail_price {
+            updated.retail_price = retail_price;
+        }
+        if let Some(change) = update.quantity {
+            change.amount().check_precision(product.unit)?;
+            updated.quantity = change.apply(product)?;
+        }
+        if let Some(description) = update.description {
+            updated.description = description;
+        }
+        updated.validate()?;
+        updated.reconcile_cost_layers(costing_method);
+
+        let parent_price = product
+            .variant
+            .as_ref()
+            .and_then(|v| self.get_item(&v.parent))
+            .map(|parent| parent.retail_price);
+        let is_variant = product.variant.is_some();
+        let name = product.name.clone();
+        if let (Some(retail_price), Some(variant)) = (update.retail_price, updated.variant.as_mut())
+        {
+            variant.price_override = (Some(retail_price) != parent_price).then_some(retail_price);
+        }
+        self.update_product(index, |product| *product = updated);
+
+        if let Some(retail_price) = update.retail_price.filter(|_| !is_variant) {
+            for index in self.variant_positions(&name) {
+                self.update_product(index, |p| {
+                    if p.variant
+                        .as_ref()
+                        .is_some_and(|v| v.price_override.is_none())
+                    {
+                        p.retail_price = retail_price;
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn rename_item(&mut self, name: &str, new_name: &str) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+        let new_name = &display_name(new_name);
+
+        if self.position(new_name).is_some_and(|i| i != index) {
+            return Err(StoreError::already_exists(Entity::Product, new_name));
+        }
+
+        let mut renamed = self.products()[index].clone();
+        if renamed.name == *new_name {
+            return Ok(());
+        }
+        renamed
+            .aliases
+            .retain(|alias| name_key(alias) != name_key(new_name));
+        // A change of case only keeps the same identity, so needs no alias.
+        if name_key(&renamed.name) != name_key(new_name) {
+            renamed.aliases.push(renamed.name.clone());
+        }
+        renamed.name = new_name.to_string();
+
+        renamed.validate()?;
+
+        let old_name = self.update_product(index, |p| std::mem::replace(p, renamed).name);
+        for index in self.variant_positions(&old_name) {
+            self.update_product(index, |p| {
+                if let Some(variant) = p.variant.as_mut() {
+                    variant.parent = new_name.to_string();
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn define_pack(
+        &mut self,
+        name: &str,
+        pack: &str,
+        size: Quantity,
+        of: Option<&str>,
+    ) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+        let product = &self.products()[index];
+
+        if pack.trim().is_empty() {
+            return Err(StoreError::invalid_field(
+                "pack",
+                "Pack name cannot be empty",
+            ));
+        }
+        if size <= Quantity::ZERO {
+            return Err(StoreError::invalid_field(
+                "size",
+                format!("Pack {pack} must hold more than 0"),
+            ));
+        }
+
+        let mut packs = product.packs.clone();
+        let definition = Pack {
+            name: pack.to_string(),
+            size,
+            of: of.map(str::to_string),
+        };
+        match packs.iter_mut().find(|p| p.name == pack) {
+            Some(existing) => *existing = definition,
+            None => packs.push(definition),
+        }
+        for defined in &packs {
+            pack_size(&packs, &defined.name)?.check_precision(product.unit)?;
+        }
+
+        self.update_product(index, |p| p.packs = packs);
+        Ok(())
+    }
+
+    fn remove_pack(&mut self, name: &str, pack: &str) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+        let product = &self.products()[index];
+
+        let pack_index = product
+            .packs
+            .iter()
+            .position(|p| p.name == pack)
+            .ok_or_else(|| StoreError::not_on_product(&product.name, Entity::Pack, pack))?;
+        if let Some(outer) = product.packs.iter().find(|p| p.of.as_deref() == Some(pack)) {
+            return Err(StoreError::invalid(format!(
+                "Pack {pack} cannot be removed while pack {} is made of it",
+                outer.name
+            )));
+        }
+
+        self.update_product(index, |p| p.packs.remove(pack_index));
+        Ok(())
+    }
+
+    fn add_variant(
+        &mut self,
+        parent: &str,
+        sku: &str,
+        options: BTreeMap<String, String>,
+        quantity: Quantity,
+        cost_price: Option<Money>,
+        retail_price: Option<Money>,
+    ) -> Result<String, StoreError> {
+        let parent = &self.products()[position_of(self, parent)?];
+        if parent.variant.is_some() {
+            return Err(StoreError::invalid(format!(
+                "Product {} is a variant and cannot have variants",
+                parent.name
+            )));
+        }
+        let sku = &display_name(sku);
+        if sku.is_empty() {
+            return Err(StoreError::invalid_field("sku", "SKU cannot be empty"));
+        }
+        if options.is_empty() {
+            return Err(StoreError::invalid_field(
+                "options",
+                "A variant needs at least one option",
+            ));
+        }
+        if self.get_item(sku).is_some() {
+            return Err(StoreError::already_exists(Entity::Sku, sku));
+        }
+        if self
+            .variants_of(&parent.name)
+            .iter()
+            .any(|v| v.variant.as_ref().is_some_and(|v| v.options == options))
+        {
+            return Err(StoreError::invalid(format!(
+                "Product {} already has this variant",
+                parent.name
+            )));
+        }
+
+        let name = format!(
+            "{} ({})",
+            parent.name,
+            options.values().cloned().collect::<Vec<_>>().join(", ")
+        );
+        if self.get_item(&name).is_some() {
+            return Err(StoreError::already_exists(Entity::Product, &name));
+        }
+        quantity.check_precision(parent.unit)?;
+
+        let mut variant = Product {
+            name: name.clone(),
+            cost_price: cost_price.unwrap_or(parent.cost_price),
+            retail_price: retail_price.unwrap_or(parent.retail_price),
             quantity,
-            description: description.to_string(),
+            unit: parent.unit,
+            packs: parent.packs.clone(),
+            description: parent.description.clone(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: parent.category.clone(),
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: Some(Variant {
+                parent: parent.name.clone(),
+                sku: sku.to_string(),
+                options,
+                price_override: retail_price,
+            }),
+            cost_layers: Vec::new(),
         };
+        variant.reconcile_cost_layers(CostingMethod::default());
+        variant.validate()?;
+
+        self.insert_product(variant);
+        Ok(name)
+    }
 
-        temp_product
-            .validate()
-            .map_err(|errors| format!("Validation errors: {errors:#?}"))?;
+    fn variants_of(&self, name: &str) -> Vec<&Product> {
+        self.variant_positions(name)
+            .into_iter()
+            .map(|index| &self.products()[index])
+            .collect()
+    }
+
+    fn set_attribute(
+        &mut self,
+        name: &str,
+        attribute: &str,
+        value: &str,
+        schema: &[AttributeDefinition],
+    ) -> Result<AttributeValue, StoreError> {
+        let definition = schema
+            .iter()
+            .find(|a| a.name == attribute)
+            .ok_or_else(|| StoreError::not_found(Entity::Attribute, attribute))?;
+        let value = definition
+            .kind
+            .parse(value)
+            .map_err(|message| StoreError::invalid_field(attribute, message))?;
+        let index = position_of(self, name)?;
+
+        self.update_product(index, |product| {
+            product
+                .attributes
+                .insert(attribute.to_string(), value.clone())
+        });
+        Ok(value)
+    }
 
-        match self.iter_mut().find(|p| p.name == name) {
-            Some(product) => {
-                product.price = price;
-                product.quantity = quantity;
-                product.description = description.to_string();
-                Ok(())
+    fn unset_attribute(&mut self, name: &str, attribute: &str) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+        self.update_product(index, |product| {
+            product
+                .attributes
+                .remove(attribute)
+                .map(|_| ())
+                .ok_or_else(|| {
+                    StoreError::not_on_product(&product.name, Entity::Attribute, attribute)
+                })
+        })
+    }
+
+    fn add_tag(&mut self, name: &str, tag: &str) -> Result<(), StoreError> {
+        let tag = normalize_tag(tag);
+        if tag.is_empty() || tag.chars().count() > 50 {
+            return Err(StoreError::invalid_field(
+                "tag",
+                "Tag must be 1-50 characters",
+            ));
+        }
+        let index = position_of(self, name)?;
+
+        self.update_product(index, |product| {
+            if !product.has_tag(&tag) {
+                product.tags.push(tag);
             }
-            None => Err(format!("Product {name} not found")),
+        });
+        Ok(())
+    }
+
+    fn remove_tag(&mut self, name: &str, tag: &str) -> Result<(), StoreError> {
+        let index = position_of(self, name)?;
+        let product = &self.products()[index];
+        if !product.has_tag(tag) {
+            return Err(StoreError::not_on_product(&product.name, Entity::Tag, tag));
+        }
+
+        let tag = normalize_tag(tag);
+        self.update_product(index, |product| product.tags.retain(|t| *t != tag));
+        Ok(())
+    }
+
+    fn find_variant(
+        &self,
+        parent: &str,
+        options: &[(String, String)],
+    ) -> Result<&Product, StoreError> {
+        let parent = &self.products()[position_of(self, parent)?];
+        let matching: Vec<&Product> = self
+            .variants_of(&parent.name)
+            .into_iter()
+            .filter(|p| {
+                p.variant.as_ref().is_some_and(|v| {
+                    options
+                        .iter()
+                        .all(|(key, value)| v.options.get(key) == Some(value))
+                })
+            })
+            .collect();
+        let described = options
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        match matching.as_slice() {
+            [variant] => Ok(variant),
+            [] => Err(StoreError::not_on_product(
+                &parent.name,
+                Entity::Variant,
+                &described,
+            )),
+            _ => Err(StoreError::invalid(format!(
+                "{} variants of {} match {described}; give more options",
+                matching.len(),
+                parent.name
+            ))),
         }
     }
 
     fn get_item(&self, name: &str) -> Option<&Product> {
-        self.iter().find(|p| p.name == name)
+        self.position(name).map(|index| &self.products()[index])
     }
 }
 
+// The plain store: a list of products searched from the start on every lookup.
+impl Inventory for Vec<Product> {
+    fn products(&self) -> &[Product] {
+        self
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.iter().position(|p| p.matches(name))
+    }
+
+    fn variant_positions(&self, parent: &str) -> Vec<usize> {
+        self.iter()
+            .enumerate()
+            .filter(|(_, p)| p.is_variant_of(parent))
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn insert_product(&mut self, product: Product) {
+        self.push(product);
+    }
+
+    fn update_product<R>(&mut self, index: usize, update: impl FnOnce(&mut Product) -> R) -> R {
+        update(&mut self[index])
+    }
+
+    fn take_product(&mut self, index: usize) -> Product {
+        self.remove(index)
+    }
+
+    fn search_index(&self) -> Cow<'_, SearchIndex> {
+        Cow::Owned(SearchIndex::new(self))
+    }
+}
 
+#[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_add_item() -> Result<(), String> {
+    fn test_add_item() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
-        inventory.add_item("Test Product", 10.0, 5, "Test description")?;
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
         assert_eq!(inventory.get_item("Test Product").unwrap(), &Product {
             name: "Test Product".to_string(),
-            price: 10.0,
-            quantity: 5,
+            cost_price: Money::from_cents(600),
+            retail_price: Money::from_cents(1000),
+            quantity: 5.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
             description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: vec![CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(600) }],
         });
         Ok(())
     }
 
     #[test]
-    fn test_remove_item() -> Result<(), String> {
+    fn test_remove_item() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
-        inventory.add_item("Test Product", 10.0, 5, "Test description")?;
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
         inventory.remove_item("Test Product")?;
-        assert!(inventory.get_item("Test Product").is_none());
+        assert_eq!(inventory.get_item("Test Product").unwrap().status, ProductStatus::Archived);
         Ok(())
     }
 
     #[test]
-    fn test_update_item() -> Result<(), String> {
+    fn test_update_item() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
-        inventory.add_item("Test Product", 10.0, 5, "Test description")?;
-        inventory.update_item("Test Product", 20.0, 10, "Updated description")?;
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        inventory.update_item("Test Product", ProductUpdate { cost_price: Some(Money::from_cents(1200)), retail_price: Some(Money::from_cents(2000)), quantity: Some(QuantityChange::Set(10.into())), description: Some("Updated description".to_string()) }, CostingMethod::Fifo)?;
         assert_eq!(inventory.get_item("Test Product").unwrap(), &Product {
             name: "Test Product".to_string(),
-            price: 20.0,
-            quantity: 10,
+            cost_price: Money::from_cents(1200),
+            retail_price: Money::from_cents(2000),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
             description: "Updated description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: vec![
+                CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(600) },
+                CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(1200) },
+            ],
         });
         Ok(())
     }
 
     #[test]
-    fn test_get_item() -> Result<(), String> {
+    fn test_update_item_partially() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        inventory.update_item("Test Product", ProductUpdate { description: Some("Fixed description".to_string()), ..Default::default() }, CostingMethod::Fifo)?;
+        inventory.update_item("Test Product", ProductUpdate { quantity: Some("+5".parse().unwrap()), ..Default::default() }, CostingMethod::Fifo)?;
+        inventory.update_item("Test Product", ProductUpdate { quantity: Some("-3".parse().unwrap()), ..Default::default() }, CostingMethod::Fifo)?;
+        let product = inventory.get_item("Test Product").unwrap();
+        assert_eq!((product.quantity, product.retail_price), (7.into(), Money::from_cents(1000)));
+        assert_eq!(product.description, "Fixed description");
+
+        let error = inventory.update_item("Test Product", ProductUpdate { quantity: Some(QuantityChange::Remove(8.into())), ..Default::default() }, CostingMethod::Fifo).unwrap_err();
+        assert!(matches!(error, StoreError::InsufficientStock { .. }));
+        assert!(inventory.update_item("Test Product", ProductUpdate::default(), CostingMethod::Fifo).is_err());
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 7.into());
+        for change in ["--5", "+-5", "-+5", "++5"] {
+            assert_eq!(change.parse::<QuantityChange>(), Err(format!("Invalid quantity: {change}")));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_item() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
-        inventory.add_item("Test Product", 10.0, 5, "Test description")?;
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
         assert_eq!(inventory.get_item("Test Product").unwrap(), &Product {
             name: "Test Product".to_string(),
-            price: 10.0,
-            quantity: 5,
+            cost_price: Money::from_cents(600),
+            retail_price: Money::from_cents(1000),
+            quantity: 5.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
             description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: vec![CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(600) }],
         });
         Ok(())
     }
@@ -159,6 +853,139 @@ mod tests {
     #[test]
     fn test_add_invalid_item()  {
         let mut inventory: Vec<Product> = Vec::new();
-        assert!(inventory.add_item("", 10.0, 5, "Test description").is_err());
+        assert!(inventory.add_item("", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description").is_err());
+    }
+
+    #[test]
+    fn test_rename_item() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        inventory.rename_item("Test Product", "Renamed Product")?;
+        let product = inventory.get_item("Renamed Product").unwrap();
+        assert_eq!(product.aliases, vec!["Test Product".to_string()]);
+        assert_eq!(inventory.get_item("Test Product").unwrap().name, "Renamed Product");
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_identity() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item(" Caf\u{e9} Latte ", Money::from_cents(100), Money::from_cents(300), 5.into(), Unit::Each, "Coffee")?;
+        assert!(inventory.add_item("cafe\u{301} latte", Money::from_cents(100), Money::from_cents(300), 5.into(), Unit::Each, "Coffee").is_err());
+        assert_eq!(inventory.get_item("CAF\u{c9} LATTE").unwrap().name, "Caf\u{e9} Latte");
+        inventory.rename_item("café latte", "Café latte")?;
+        let product = inventory.get_item("Café Latte").unwrap();
+        assert_eq!(product.name, "Café latte");
+        assert!(product.aliases.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_item_to_existing_name() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        inventory.add_item("Other Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        assert!(inventory.rename_item("Test Product", "Other Product").is_err());
+        assert!(inventory.get_item("Test Product").unwrap().aliases.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_purge_item() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        inventory.purge_item("Test Product", &[], &[])?;
+        assert!(inventory.get_item("Test Product").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_purge_item_with_history() -> Result<(), StoreError> {
+        use crate::costing::CostingMethod;
+        use crate::currency::Conversion;
+        use crate::sales::Sales;
+
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut sales: Vec<Sale> = Vec::new();
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        sales.record_sale("Test Product", 1.into(), None, None, &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert!(inventory.purge_item("Test Product", &sales, &[]).is_err());
+        assert!(inventory.get_item("Test Product").is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_define_pack() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Test Product", Money::from_cents(50), Money::from_cents(100), 48.into(), Unit::Each, "Test description")?;
+        inventory.define_pack("Test Product", "case", 24.into(), None)?;
+        inventory.define_pack("Test Product", "pallet", 40.into(), Some("case"))?;
+        assert_eq!(inventory.get_item("Test Product").unwrap().pack_size("pallet")?, 960.into());
+        assert!(inventory.define_pack("Test Product", "case", 2.into(), Some("pallet")).is_err());
+        assert!(inventory.define_pack("Test Product", "crate", 12.into(), Some("box")).is_err());
+        assert!(inventory.remove_pack("Test Product", "case").is_err());
+        inventory.remove_pack("Test Product", "pallet")?;
+        assert_eq!(inventory.get_item("Test Product").unwrap().packs.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_category() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let categories = vec![Category { name: "Citrus".to_string(), parent: None }];
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        inventory.set_category("Test Product", Some("Citrus"), &categories)?;
+        assert_eq!(inventory.get_item("Test Product").unwrap().category.as_deref(), Some("Citrus"));
+        assert!(inventory.set_category("Test Product", Some("Dairy"), &categories).is_err());
+        inventory.set_category("Test Product", None, &categories)?;
+        assert!(inventory.get_item("Test Product").unwrap().category.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_variant() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("T-Shirt", Money::from_cents(600), Money::from_cents(1500), 0.into(), Unit::Each, "Cotton T-shirt")?;
+        let medium = BTreeMap::from([("size".to_string(), "M".to_string())]);
+        let large = BTreeMap::from([("size".to_string(), "L".to_string())]);
+        inventory.add_variant("T-Shirt", "TS-M", medium.clone(), 10.into(), None, None)?;
+        inventory.add_variant("T-Shirt", "TS-L", large, 5.into(), None, Some(Money::from_cents(1700)))?;
+        assert!(inventory.add_variant("T-Shirt", "TS-M2", medium, 5.into(), None, None).is_err());
+        assert_eq!(inventory.get_item("TS-M").unwrap().name, "T-Shirt (M)");
+
+        inventory.update_item("T-Shirt", ProductUpdate { retail_price: Some(Money::from_cents(1600)), ..Default::default() }, CostingMethod::Fifo)?;
+        assert_eq!(inventory.get_item("TS-M").unwrap().retail_price, Money::from_cents(1600));
+        assert_eq!(inventory.get_item("TS-L").unwrap().retail_price, Money::from_cents(1700));
+
+        let options = vec![("size".to_string(), "L".to_string())];
+        assert_eq!(inventory.find_variant("T-Shirt", &options)?.name, "T-Shirt (L)");
+        assert!(inventory.purge_item("T-Shirt", &[], &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_attributes_and_tags() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::attributes::AttributeType;
+        use crate::filter::{ProductFilter, parse_attribute_filter};
+
+        let mut inventory: Vec<Product> = Vec::new();
+        let schema = vec![AttributeDefinition { name: "organic".to_string(), kind: AttributeType::Bool }];
+        inventory.add_item("Test Product", Money::from_cents(600), Money::from_cents(1000), 5.into(), Unit::Each, "Test description")?;
+        assert!(inventory.set_attribute("Test Product", "organic", "maybe", &schema).is_err());
+        assert!(inventory.set_attribute("Test Product", "brand", "Acme", &schema).is_err());
+        inventory.set_attribute("Test Product", "organic", "true", &schema)?;
+        inventory.add_tag("Test Product", " Vegan ")?;
+        assert!(inventory.add_tag("Test Product", "   ").is_err());
+        assert!(inventory.add_tag("Test Product", &"é".repeat(51)).is_err());
+        inventory.add_tag("Test Product", &"é".repeat(50))?;
+        inventory.remove_tag("Test Product", &"é".repeat(50))?;
+
+        let product = inventory.get_item("Test Product").unwrap();
+        assert_eq!(product.tags, vec!["vegan".to_string()]);
+        let filter = ProductFilter { tags: vec!["VEGAN".to_string()], attributes: vec![parse_attribute_filter("organic=true")?], ..Default::default() };
+        assert!(filter.matches(product));
+        let filter = ProductFilter { tags: Vec::new(), attributes: vec![parse_attribute_filter("organic=false")?], ..Default::default() };
+        assert!(!filter.matches(product));
+        Ok(())
     }
 }
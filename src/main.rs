@@ -1,20 +1,55 @@
+use chrono::Utc;
 use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
+mod attributes;
 mod auth;
+mod category;
+mod costing;
+mod currency;
+mod diff;
+mod error;
+mod filter;
+mod indexed;
 mod inventory;
+mod merge;
+mod money;
+mod output;
 mod persistence;
 mod purchase;
 mod report;
 mod sales;
+mod script;
+mod search;
+mod settings;
+mod shell;
+mod store;
+mod tui;
+mod units;
 
-use crate::auth::Auth;
-use crate::inventory::{Inventory, Product};
-use crate::persistence::{
-    load_inventory, load_purchases, load_sales, save_inventory, save_purchases, save_sales,
+use crate::attributes::{AttributeSchema, AttributeType};
+use crate::auth::{Auth, Role};
+use crate::category::{Categories, CategoryLookup};
+use crate::costing::CostingMethod;
+use crate::currency::{find_conversion, parse_currency};
+use crate::diff::StoreDiff;
+use crate::error::StoreError;
+use crate::filter::{AttributeFilter, ProductFilter, SortField, page, parse_attribute_filter};
+use crate::inventory::{
+    Inventory, Product, ProductStatus, ProductUpdate, QuantityChange, parse_variant_option,
 };
+use crate::merge::{PriceChoice, merge_products};
+use crate::money::Money;
+use crate::output::{Output, OutputFormat};
 use crate::purchase::{Purchase, Purchases};
-use crate::report::Reporter;
+use crate::report::{CategoryRow, PlainRecord, ProductListing, ProductRow, ReportData, Reporter};
 use crate::sales::{Sale, Sales};
+use crate::search::{near_duplicates, product_not_found};
+use crate::store::Store;
+use crate::units::{Quantity, Unit};
 
 #[derive(Parser)]
 #[command(name = "store management system")]
@@ -24,6 +59,13 @@ use crate::sales::{Sale, Sales};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    // No short flag: -o is taken by add-variant's --option.
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Table)]
+    output: OutputFormat,
+    // Run the command against a copy of the store and show what it would change,
+    // without saving anything.
+    #[arg(long, global = true)]
+    dry_run: bool,
 }
 
 #[derive(Subcommand)]
@@ -32,9 +74,13 @@ enum Commands {
         #[arg(short, long)]
         name: String,
         #[arg(short, long)]
-        price: f64,
+        cost_price: Money,
+        #[arg(short = 'p', long)]
+        retail_price: Money,
         #[arg(short, long)]
-        quantity: u32,
+        quantity: Quantity,
+        #[arg(short, long, default_value = "each")]
+        unit: Unit,
         #[arg(short, long)]
         description: String,
     },
@@ -42,44 +88,262 @@ enum Commands {
         #[arg(short, long)]
         name: String,
     },
+    SetStatus {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        status: ProductStatus,
+    },
+    PurgeProduct {
+        #[arg(short, long)]
+        name: String,
+    },
+    // Only the fields given are changed. The quantity is a new stock level, or an
+    // adjustment such as +5 or -3.
     UpdateProduct {
         #[arg(short, long)]
         name: String,
         #[arg(short, long)]
-        price: f64,
+        cost_price: Option<Money>,
+        #[arg(short = 'p', long)]
+        retail_price: Option<Money>,
+        #[arg(short, long, allow_hyphen_values = true)]
+        quantity: Option<QuantityChange>,
         #[arg(short, long)]
-        quantity: u32,
+        description: Option<String>,
+    },
+    RenameProduct {
         #[arg(short, long)]
-        description: String,
+        name: String,
+        #[arg(short = 'N', long)]
+        new_name: String,
+    },
+    MergeProducts {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        from: String,
+        #[arg(long)]
+        prices: Option<PriceChoice>,
     },
     ShowProduct {
         #[arg(short, long)]
         name: String,
     },
+    ListProducts {
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(long)]
+        min_price: Option<Money>,
+        #[arg(long)]
+        max_price: Option<Money>,
+        #[arg(long)]
+        min_quantity: Option<Quantity>,
+        #[arg(long)]
+        max_quantity: Option<Quantity>,
+        #[arg(short = 'C', long)]
+        category: Option<String>,
+        #[arg(short, long)]
+        status: Option<ProductStatus>,
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        #[arg(short, long = "attribute", value_parser = parse_attribute_filter)]
+        attributes: Vec<AttributeFilter>,
+        #[arg(long, default_value = "name")]
+        sort: SortField,
+        #[arg(long)]
+        descending: bool,
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        page: u32,
+        #[arg(long, default_value_t = 20, value_parser = clap::value_parser!(u32).range(1..))]
+        per_page: u32,
+    },
+    Search {
+        #[arg(short, long)]
+        query: String,
+        #[arg(short, long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        limit: u32,
+    },
+    AddVariant {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short = 'k', long)]
+        sku: String,
+        #[arg(short, long = "option", value_parser = parse_variant_option, required = true)]
+        options: Vec<(String, String)>,
+        #[arg(short, long)]
+        quantity: Quantity,
+        #[arg(short, long)]
+        cost_price: Option<Money>,
+        #[arg(short = 'p', long)]
+        retail_price: Option<Money>,
+    },
+    DefineAttribute {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short = 't', long = "type")]
+        kind: AttributeType,
+    },
+    RemoveAttribute {
+        #[arg(short, long)]
+        name: String,
+    },
+    SetAttribute {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        attribute: String,
+        #[arg(short, long)]
+        value: String,
+    },
+    UnsetAttribute {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        attribute: String,
+    },
+    AddTag {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        tag: String,
+    },
+    RemoveTag {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        tag: String,
+    },
+    SetCategory {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short = 'C', long)]
+        category: Option<String>,
+    },
+    AddCategory {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        parent: Option<String>,
+    },
+    RenameCategory {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short = 'N', long)]
+        new_name: String,
+    },
+    MoveCategory {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short, long)]
+        parent: Option<String>,
+    },
+    RemoveCategory {
+        #[arg(short, long)]
+        name: String,
+    },
+    ListCategories,
+    DefinePack {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short = 'P', long)]
+        pack: String,
+        #[arg(short, long)]
+        size: Quantity,
+        #[arg(short, long)]
+        of: Option<String>,
+    },
+    RemovePack {
+        #[arg(short, long)]
+        name: String,
+        #[arg(short = 'P', long)]
+        pack: String,
+    },
     RecordPurchase {
         #[arg(short = 'n', long)]
         product_name: String,
         #[arg(short = 'q', long)]
-        quantity: u32,
+        quantity: Quantity,
+        #[arg(short = 'u', long)]
+        unit: Option<Unit>,
+        #[arg(short = 'P', long, conflicts_with = "unit")]
+        pack: Option<String>,
         #[arg(short = 'p', long)]
-        purchase_price: f64,
+        purchase_price: Money,
+        #[arg(short = 'r', long)]
+        retail_price: Option<Money>,
         #[arg(short = 'd', long)]
         description: String,
+        #[arg(short = 'c', long, value_parser = parse_currency)]
+        currency: Option<String>,
     },
     RecordSale {
         #[arg(short = 'n', long)]
         product_name: String,
         #[arg(short = 'q', long)]
-        quantity: u32,
+        quantity: Quantity,
+        #[arg(short = 'u', long)]
+        unit: Option<Unit>,
+        #[arg(short = 'V', long = "variant", value_parser = parse_variant_option)]
+        variant: Vec<(String, String)>,
         #[arg(short = 's', long)]
-        sale_price: f64,
+        sale_price: Option<Money>,
+        #[arg(short = 'c', long, value_parser = parse_currency)]
+        currency: Option<String>,
     },
     Report {
         #[arg(short, long)]
         report_type: ReportType,
+        #[arg(short, long)]
+        by_category: bool,
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        #[arg(short, long = "attribute", value_parser = parse_attribute_filter)]
+        attributes: Vec<AttributeFilter>,
+    },
+    Check,
+    SetCostingMethod {
+        #[arg(short, long)]
+        method: CostingMethod,
+    },
+    RecomputeProfit {
+        #[arg(short, long)]
+        method: Option<CostingMethod>,
+    },
+    SetBaseCurrency {
+        #[arg(short, long, value_parser = parse_currency)]
+        currency: String,
+    },
+    // Logs in once and reads further commands interactively.
+    Shell,
+    // Opens the full-screen terminal interface.
+    Tui,
+    // Runs a file of commands, one per line, and saves only if all of them succeed.
+    Run {
+        file: PathBuf,
+        // Undo and skip failing lines instead of stopping at the first one.
+        #[arg(long)]
+        skip_errors: bool,
     },
 }
 
+impl Commands {
+    // Whether the command can change the store's data, and so needs a save afterwards.
+    fn changes_data(&self) -> bool {
+        !matches!(
+            self,
+            Commands::ShowProduct { .. }
+                | Commands::ListProducts { .. }
+                | Commands::Search { .. }
+                | Commands::ListCategories
+                | Commands::Report { .. }
+                | Commands::Check
+                | Commands::Shell
+                | Commands::Tui
+        )
+    }
+}
+
 #[derive(ValueEnum, Clone)]
 enum ReportType {
     Inventory,
@@ -87,95 +351,729 @@ enum ReportType {
     Purchase,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("=== Store Management System ===");
-    Auth::authenticate()?;
+// The process exits with the code of the error that stopped a command, so scripts can
+// tell failures apart; nothing is saved after a failure.
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let output = Output::new(cli.output);
+    match run(cli.command, output, cli.dry_run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            output.error(&err);
+            ExitCode::from(err.exit_code())
+        }
+    }
+}
 
-    let mut inventory: Vec<Product> = load_inventory()?;
-    let mut sales: Vec<Sale> = load_sales()?;
-    let mut purchases: Vec<Purchase> = load_purchases()?;
+fn run(command: Commands, output: Output, dry_run: bool) -> Result<(), StoreError> {
+    if output.format == OutputFormat::Table {
+        println!("=== Store Management System ===");
+    }
+    let role = Auth::authenticate()?;
+    let mut store = Store::load()?;
+    match command {
+        Commands::Shell => return shell::run(store, role, output, dry_run),
+        Commands::Tui => return tui::run(store, dry_run),
+        _ => {}
+    }
 
-    match Cli::parse().command {
+    let changes_data = command.changes_data();
+    if dry_run && changes_data {
+        return preview(command, &store, role, output);
+    }
+    execute(command, &mut store, role, output)?;
+    if changes_data {
+        store.save()?;
+    }
+    Ok(())
+}
+
+// Runs a command against a copy of the store and shows what it changed in the copy. The
+// store itself is left as it was, so nothing is saved.
+fn preview(command: Commands, store: &Store, role: Role, output: Output) -> Result<(), StoreError> {
+    execute_dry_run(command, &mut store.clone(), role, output)
+}
+
+// Runs a command against the store in memory and shows what it changed, after the
+// command's own output; saving is left to the caller. In JSON the two are printed as one
+// object, {"result": ..., "dry_run": ...}.
+fn execute_dry_run(
+    command: Commands,
+    store: &mut Store,
+    role: Role,
+    output: Output,
+) -> Result<(), StoreError> {
+    let before = store.clone();
+    let (result, captured) = Output::capture(|| execute(command, store, role, output));
+    output.print(&captured.text);
+    result?;
+    let diff = StoreDiff::between(&before, store)?;
+    match output.format {
+        OutputFormat::Table => {
+            output.line("Dry run, nothing was saved. Changes:");
+            output.print(&diff.generate_text());
+        }
+        OutputFormat::Plain => output.print(&diff.generate_plain()),
+        OutputFormat::Json => {
+            #[derive(Serialize)]
+            struct DryRun<'a> {
+                result: Value,
+                dry_run: &'a StoreDiff,
+            }
+            output.json(&DryRun {
+                result: captured.into_json(),
+                dry_run: &diff,
+            })?
+        }
+    }
+    Ok(())
+}
+
+// Runs one command against the store in memory; saving is left to the caller.
+fn execute(
+    command: Commands,
+    store: &mut Store,
+    role: Role,
+    output: Output,
+) -> Result<(), StoreError> {
+    // A script works on the store as a whole, to undo its commands when one fails.
+    let command = match command {
+        Commands::Run { file, skip_errors } => {
+            return script::run(&file, skip_errors, store, role, output);
+        }
+        command => command,
+    };
+    let Store {
+        inventory,
+        sales,
+        purchases,
+        settings,
+        exchange_rates,
+        categories,
+        merges,
+    } = store;
+
+    match command {
         Commands::AddProduct {
             name,
-            price,
+            cost_price,
+            retail_price,
             quantity,
+            unit,
             description,
-        } => match inventory.add_item(&name, price, quantity, &description) {
-            Ok(()) => println!("✓ Product {name} successfully added"),
-            Err(err) => println!("⛌ {err}"),
-        },
-        Commands::RemoveProduct { name } => match inventory.remove_item(&name) {
-            Ok(()) => println!("✓ Product {name} successfully removed"),
-            Err(err) => println!("⛌ {err}"),
-        },
+        } => {
+            inventory.add_item(
+                &name,
+                cost_price,
+                retail_price,
+                quantity,
+                unit,
+                &description,
+            )?;
+            output.done(
+                &format!("Product {name} successfully added"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::RemoveProduct { name } => {
+            inventory.remove_item(&name)?;
+            output.done(
+                &format!("Product {name} successfully archived"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::SetStatus { name, status } => {
+            inventory.set_status(&name, status)?;
+            output.done(
+                &format!("Product {name} status set to {status}"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::PurgeProduct { name } => {
+            require_manager(role, "purge products")?;
+            let product = inventory.get_item(&name).cloned();
+            inventory.purge_item(&name, sales, purchases)?;
+            output.done(&format!("Product {name} successfully purged"), &product)?;
+        }
         Commands::UpdateProduct {
             name,
-            price,
+            cost_price,
+            retail_price,
             quantity,
             description,
-        } => match inventory.update_item(&name, price, quantity, &description) {
-            Ok(()) => println!("✓ Product {name} successfully updated"),
-            Err(err) => println!("⛌ {err}"),
-        },
-        Commands::ShowProduct { name } => match inventory.get_item(&name) {
-            Some(product) => println!("{product:?}"),
-            None => println!("Product {name} not found"),
-        },
+        } => {
+            let update = ProductUpdate {
+                cost_price,
+                retail_price,
+                quantity,
+                description,
+            };
+            inventory.update_item(&name, update, settings.costing_method)?;
+            output.done(
+                &format!("Product {name} successfully updated"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::RenameProduct { name, new_name } => {
+            inventory.rename_item(&name, &new_name)?;
+            if let Some(product) = inventory.get_item(&new_name) {
+                sales.relink_product(product);
+                purchases.relink_product(product);
+            }
+            output.done(
+                &format!("Product {name} successfully renamed to {new_name}"),
+                &inventory.get_item(&new_name),
+            )?;
+        }
+        Commands::MergeProducts { name, from, prices } => {
+            require_manager(role, "merge products")?;
+            let record = merge_products(
+                &name,
+                &from,
+                prices,
+                settings.costing_method,
+                inventory,
+                sales,
+                purchases,
+            )?;
+            output.done(
+                &format!("Product {} merged into {}", record.merged, record.survivor),
+                &record,
+            )?;
+            output.detail(
+                "Stock moved",
+                format!("{} {}", record.quantity, record.unit),
+            );
+            output.detail(
+                "Prices",
+                format!(
+                    "cost {} | retail {}",
+                    record.prices.cost_price, record.prices.retail_price
+                ),
+            );
+            output.detail(
+                "Sales moved",
+                format!(
+                    "{} | Purchases moved: {}",
+                    record.sales_moved, record.purchases_moved
+                ),
+            );
+            merges.push(record);
+        }
+        Commands::ShowProduct { name } => {
+            let product = inventory
+                .get_item(&name)
+                .ok_or_else(|| product_not_found(&name, inventory))?;
+            if output.is_json() {
+                output.json(product)?;
+            } else {
+                output.fields(&Reporter::product_details(product, &settings.base_currency));
+            }
+        }
+        Commands::ListProducts {
+            name,
+            min_price,
+            max_price,
+            min_quantity,
+            max_quantity,
+            category,
+            status,
+            tags,
+            attributes,
+            sort,
+            descending,
+            page: number,
+            per_page,
+        } => {
+            let categories = match category {
+                Some(category) => categories.with_subcategories(&category)?,
+                None => Vec::new(),
+            };
+            let filter = ProductFilter {
+                name,
+                min_price,
+                max_price,
+                min_quantity,
+                max_quantity,
+                categories,
+                status,
+                tags,
+                attributes,
+            };
+            // The category and tag indexes narrow down the products to check.
+            let candidates: Vec<&Product> = if !filter.categories.is_empty() {
+                filter
+                    .categories
+                    .iter()
+                    .flat_map(|c| inventory.in_category(c))
+                    .collect()
+            } else if let Some(tag) = filter.tags.first() {
+                inventory.tagged(tag)
+            } else {
+                inventory.products().iter().collect()
+            };
+            // Archived products are only listed when asked for by status.
+            let mut products: Vec<&Product> = candidates
+                .into_iter()
+                .filter(|p| filter.matches(p) && (status.is_some() || !p.is_archived()))
+                .collect();
+            products.sort_by(|a, b| {
+                let ordering = sort.compare(a, b);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+            let (number, per_page) = (number as usize, per_page as usize);
+            let listing = ProductListing {
+                page: number,
+                per_page,
+                total: products.len(),
+                products: page(&products, number, per_page)
+                    .iter()
+                    .map(|p| ProductRow::from(*p))
+                    .collect(),
+            };
+            print_listing(output, &listing, &settings.base_currency)?;
+        }
+        Commands::Search { query, limit } => {
+            let hits = inventory.search_index().search(&query);
+            let listing = ProductListing {
+                page: 1,
+                per_page: limit as usize,
+                total: hits.len(),
+                products: hits
+                    .iter()
+                    .take(limit as usize)
+                    .filter_map(|hit| inventory.get_item(&hit.name))
+                    .map(ProductRow::from)
+                    .collect(),
+            };
+            print_listing(output, &listing, &settings.base_currency)?;
+        }
+        Commands::AddVariant {
+            name,
+            sku,
+            options,
+            quantity,
+            cost_price,
+            retail_price,
+        } => {
+            let variant = inventory.add_variant(
+                &name,
+                &sku,
+                options.into_iter().collect(),
+                quantity,
+                cost_price,
+                retail_price,
+            )?;
+            output.done(
+                &format!("Variant {variant} ({sku}) successfully added"),
+                &inventory.get_item(&variant),
+            )?;
+        }
+        Commands::DefineAttribute { name, kind } => {
+            settings.attributes.define_attribute(&name, kind)?;
+            output.done(
+                &format!("Attribute {name} defined as {kind}"),
+                &settings.attributes.get_attribute(&name),
+            )?;
+        }
+        Commands::RemoveAttribute { name } => {
+            let definition = settings.attributes.get_attribute(&name).cloned();
+            settings
+                .attributes
+                .remove_attribute(&name, inventory.products())?;
+            output.done(
+                &format!("Attribute {name} successfully removed"),
+                &definition,
+            )?;
+        }
+        Commands::SetAttribute {
+            name,
+            attribute,
+            value,
+        } => {
+            let value = inventory.set_attribute(&name, &attribute, &value, &settings.attributes)?;
+            output.done(
+                &format!("Product {name} {attribute} set to {value}"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::UnsetAttribute { name, attribute } => {
+            inventory.unset_attribute(&name, &attribute)?;
+            output.done(
+                &format!("Attribute {attribute} removed from product {name}"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::AddTag { name, tag } => {
+            inventory.add_tag(&name, &tag)?;
+            output.done(
+                &format!("Product {name} tagged {tag}"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::RemoveTag { name, tag } => {
+            inventory.remove_tag(&name, &tag)?;
+            output.done(
+                &format!("Tag {tag} removed from product {name}"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::SetCategory { name, category } => {
+            inventory.set_category(&name, category.as_deref(), categories)?;
+            let message = match category {
+                Some(category) => format!("Product {name} moved to category {category}"),
+                None => format!("Product {name} removed from its category"),
+            };
+            output.done(&message, &inventory.get_item(&name))?;
+        }
+        Commands::AddCategory { name, parent } => {
+            categories.add_category(&name, parent.as_deref())?;
+            output.done(
+                &format!("Category {name} successfully added"),
+                &categories.get_category(&name),
+            )?;
+        }
+        Commands::RenameCategory { name, new_name } => {
+            categories.rename_category(&name, &new_name, inventory)?;
+            output.done(
+                &format!("Category {name} successfully renamed to {new_name}"),
+                &categories.get_category(&new_name),
+            )?;
+        }
+        Commands::MoveCategory { name, parent } => {
+            categories.move_category(&name, parent.as_deref())?;
+            output.done(
+                &format!("Category {name} successfully moved"),
+                &categories.get_category(&name),
+            )?;
+        }
+        Commands::RemoveCategory { name } => {
+            let category = categories.get_category(&name).cloned();
+            categories.remove_category(&name, inventory.products())?;
+            output.done(&format!("Category {name} successfully removed"), &category)?;
+        }
+        Commands::ListCategories => {
+            let rows: Vec<CategoryRow> = categories
+                .tree()
+                .into_iter()
+                .map(|(depth, category)| CategoryRow {
+                    name: &category.name,
+                    parent: category.parent.as_deref(),
+                    depth,
+                    products: inventory.in_category(&category.name).len(),
+                })
+                .collect();
+            match output.format {
+                OutputFormat::Json => output.json(&rows)?,
+                OutputFormat::Plain => output.print(&Reporter::generate_plain(&rows)),
+                OutputFormat::Table => {
+                    if rows.is_empty() {
+                        output.line("No categories defined.");
+                    }
+                    for row in &rows {
+                        output.line(format_args!(
+                            "{}{} ({})",
+                            "  ".repeat(row.depth),
+                            row.name,
+                            row.products
+                        ));
+                    }
+                }
+            }
+        }
+        Commands::DefinePack {
+            name,
+            pack,
+            size,
+            of,
+        } => {
+            inventory.define_pack(&name, &pack, size, of.as_deref())?;
+            output.done(
+                &format!("Pack {pack} defined for product {name}"),
+                &inventory.get_item(&name),
+            )?;
+        }
+        Commands::RemovePack { name, pack } => {
+            inventory.remove_pack(&name, &pack)?;
+            output.done(
+                &format!("Pack {pack} removed from product {name}"),
+                &inventory.get_item(&name),
+            )?;
+        }
         Commands::RecordPurchase {
             product_name,
             description,
             quantity,
+            unit,
+            pack,
             purchase_price,
+            retail_price,
+            currency,
         } => {
-            match purchases.record_purchase(
-                &product_name,
-                quantity,
-                purchase_price,
-                &description,
-                &mut inventory,
-            ) {
-                Ok(purchase) => {
-                    println!("✓ Purchase recorded successfully!");
-                    println!("  Product: {}", purchase.product_name);
-                    println!("  Quantity: {}", purchase.quantity);
-                    println!("  Total: ${:.2}", purchase.total_cost);
+            let currency = currency.unwrap_or_else(|| settings.base_currency.clone());
+            // A purchase of an unknown product adds it, so a mistyped name would add a
+            // duplicate; point out close matches when that happens.
+            let suggestions = match inventory.get_item(&product_name) {
+                Some(_) => Vec::new(),
+                None => inventory.search_index().suggest(&product_name),
+            };
+            let purchase = find_conversion(
+                exchange_rates,
+                &currency,
+                &settings.base_currency,
+                Utc::now(),
+            )
+            .and_then(|conversion| {
+                purchases.record_purchase(
+                    &product_name,
+                    quantity,
+                    unit,
+                    pack.as_deref(),
+                    purchase_price,
+                    retail_price,
+                    &description,
+                    &conversion,
+                    settings.costing_method,
+                    inventory,
+                )
+            })
+            .inspect_err(|_| {
+                if !suggestions.is_empty() {
+                    output.note(&format!(
+                        "{product_name} is not in the inventory. Did you mean {}?",
+                        suggestions.join(", ")
+                    ));
                 }
-                Err(err) => println!("⛌ {err}"),
+            })?;
+            output.recorded("Purchase recorded successfully!", &purchase)?;
+            output.detail("Product", &purchase.product_name);
+            match &purchase.pack {
+                Some(pack) => output.detail(
+                    "Quantity",
+                    format!(
+                        "{} {} ({} {})",
+                        purchase.quantity, purchase.unit, pack.quantity, pack.pack
+                    ),
+                ),
+                None => output.detail(
+                    "Quantity",
+                    format!("{} {}", purchase.quantity, purchase.unit),
+                ),
+            }
+            output.detail(
+                "Total",
+                format!("{} {}", purchase.total_cost, purchase.currency),
+            );
+            if purchase.currency != settings.base_currency {
+                output.detail(
+                    "Base Total",
+                    format!("{} {}", purchase.base_total_cost, settings.base_currency),
+                );
+            }
+            if !suggestions.is_empty() {
+                output.note(&format!(
+                    "{} was added as a new product. Did you mean {}?",
+                    purchase.product_name,
+                    suggestions.join(", ")
+                ));
             }
         }
         Commands::RecordSale {
             product_name,
             quantity,
+            unit,
+            variant,
             sale_price,
-        } => match sales.record_sale(&product_name, quantity, sale_price, &mut inventory) {
-            Ok(sale) => {
-                println!("✓ Sale recorded successfully!");
-                println!("  Product: {}", sale.product_name);
-                println!("  Quantity: {}", sale.quantity);
-                println!("  Sale Price: ${:.2}", sale.sale_price);
-                println!("  Profit: ${:.2}", sale.profit);
+            currency,
+        } => {
+            let currency = currency.unwrap_or_else(|| settings.base_currency.clone());
+            let product_name = if variant.is_empty() {
+                product_name
+            } else {
+                inventory
+                    .find_variant(&product_name, &variant)?
+                    .name
+                    .clone()
+            };
+            let conversion = find_conversion(
+                exchange_rates,
+                &currency,
+                &settings.base_currency,
+                Utc::now(),
+            )?;
+            let sale = sales.record_sale(
+                &product_name,
+                quantity,
+                unit,
+                sale_price,
+                &conversion,
+                settings.costing_method,
+                inventory,
+            )?;
+            let base = &settings.base_currency;
+            output.recorded("Sale recorded successfully!", &sale)?;
+            output.detail("Product", &sale.product_name);
+            output.detail("Quantity", format!("{} {}", sale.quantity, sale.unit));
+            output.detail(
+                "Sale Price",
+                format!("{} {}", sale.sale_price, sale.currency),
+            );
+            if sale.currency != *base {
+                output.detail("Base Total", format!("{} {base}", sale.base_total));
             }
-            Err(err) => println!("⛌ {err}"),
-        },
-        Commands::Report { report_type } => match report_type {
-            ReportType::Inventory => {
-                let report = Reporter::generate_inventory_report(&inventory);
-                println!("{report}");
+            output.detail("COGS", format!("{} {base}", sale.cost_of_goods));
+            output.detail("Profit", format!("{} {base}", sale.profit));
+        }
+        Commands::Report {
+            report_type,
+            by_category,
+            tags,
+            attributes,
+        } => {
+            let filter = ProductFilter {
+                tags,
+                attributes,
+                ..Default::default()
+            };
+            let selected = |name: &str| {
+                filter.is_empty() || inventory.get_item(name).is_some_and(|p| filter.matches(p))
+            };
+            let lookup = CategoryLookup::new(categories, inventory.products());
+            let categories = by_category.then_some(&lookup);
+            let base = &settings.base_currency;
+            match report_type {
+                ReportType::Inventory => {
+                    let products: Vec<Product> = inventory
+                        .products()
+                        .iter()
+                        .filter(|p| selected(&p.name))
+                        .cloned()
+                        .collect();
+                    print_report(
+                        output,
+                        Reporter::inventory_data(&products, base, categories),
+                        || Reporter::generate_inventory_report(&products, base, categories),
+                    )?;
+                }
+                ReportType::Sales => {
+                    let sales: Vec<Sale> = sales
+                        .iter()
+                        .filter(|s| selected(&s.product_name))
+                        .cloned()
+                        .collect();
+                    print_report(
+                        output,
+                        Reporter::sales_data(&sales, base, categories),
+                        || Reporter::generate_sales_report(&sales, base, categories),
+                    )?;
+                }
+                ReportType::Purchase => {
+                    let purchases: Vec<Purchase> = purchases
+                        .iter()
+                        .filter(|p| selected(&p.product_name))
+                        .cloned()
+                        .collect();
+                    print_report(
+                        output,
+                        Reporter::purchase_data(&purchases, base, categories),
+                        || Reporter::generate_purchase_report(&purchases, base, categories),
+                    )?;
+                }
             }
-            ReportType::Sales => {
-                let report = Reporter::generate_sales_report(&sales);
-                println!("{report}");
+        }
+        Commands::Check => {
+            let duplicates = near_duplicates(inventory.products());
+            if output.is_json() {
+                output.json(&duplicates)?;
+            } else if output.format == OutputFormat::Plain {
+                output.print(&Reporter::generate_plain(&duplicates));
+            } else if duplicates.is_empty() {
+                output.line("✓ No near-duplicate product names found");
+            } else {
+                output.line(
+                    "Possible duplicate products, which can be combined with merge-products:",
+                );
+                for duplicate in &duplicates {
+                    output.line(format_args!(
+                        "  {} / {} ({})",
+                        duplicate.first.name, duplicate.second.name, duplicate.reason
+                    ));
+                }
             }
-            ReportType::Purchase => {
-                let report = Reporter::generate_purchase_report(&purchases);
-                println!("{report}");
+        }
+        Commands::SetCostingMethod { method } => {
+            settings.costing_method = method;
+            output.done(&format!("Costing method set to {method}"), &settings)?;
+        }
+        Commands::RecomputeProfit { method } => {
+            let method = method.unwrap_or(settings.costing_method);
+            let recomputed = sales.recompute_profits(purchases, method, inventory);
+            output.done(
+                &format!("Recomputed profit for {recomputed} sales using {method} costing"),
+                &serde_json::json!({ "recomputed": recomputed, "costing_method": method }),
+            )?;
+        }
+        Commands::SetBaseCurrency { currency } => {
+            if !sales.is_empty() || !purchases.is_empty() {
+                return Err(StoreError::invalid(
+                    "The base currency cannot be changed once sales or purchases exist",
+                ));
             }
-        },
+            settings.base_currency = currency;
+            output.done(
+                &format!("Base currency set to {}", settings.base_currency),
+                &settings,
+            )?;
+        }
+        Commands::Run { .. } => unreachable!("scripts are run before the match"),
+        Commands::Shell | Commands::Tui => {
+            return Err(StoreError::invalid(
+                "The shell and the terminal interface cannot be started from the shell",
+            ));
+        }
     }
+    Ok(())
+}
+
+fn print_listing(
+    output: Output,
+    listing: &ProductListing,
+    base_currency: &str,
+) -> Result<(), StoreError> {
+    match output.format {
+        OutputFormat::Table => {
+            output.print(&Reporter::generate_product_list(listing, base_currency))
+        }
+        OutputFormat::Plain => output.print(&Reporter::generate_plain(&listing.products)),
+        OutputFormat::Json => output.json(listing)?,
+    }
+    Ok(())
+}
 
-    save_inventory(&inventory)?;
-    save_sales(&sales)?;
-    save_purchases(&purchases)?;
+// Reports are text in table output, data in JSON output and their records alone in
+// plain output.
+fn print_report<R: Serialize + PlainRecord, T: Serialize>(
+    output: Output,
+    data: ReportData<'_, R, T>,
+    text: impl FnOnce() -> String,
+) -> Result<(), StoreError> {
+    match output.format {
+        OutputFormat::Table => output.line(text()),
+        OutputFormat::Plain => output.print(&Reporter::generate_plain(&data.records)),
+        OutputFormat::Json => output.json(&data)?,
+    }
+    Ok(())
+}
+
+fn require_manager(role: Role, action: &str) -> Result<(), StoreError> {
+    if role != Role::Manager {
+        return Err(StoreError::Unauthorized(format!(
+            "Only store managers can {action}"
+        )));
+    }
     Ok(())
 }
@@ -0,0 +1,270 @@
+use crate::costing::CostingMethod;
+use crate::error::StoreError;
+use crate::inventory::{Inventory, Product};
+use crate::money::Money;
+use crate::purchase::{Purchase, Purchases};
+use crate::sales::{Sale, Sales};
+use crate::search::product_not_found;
+use crate::units::{Quantity, Unit};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Which prices the surviving product ends up with when the two products' prices differ.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceChoice {
+    // The surviving product's own prices.
+    Keep,
+    // The prices of the product merged into it.
+    Take,
+    // Averages of both, weighted by the stock of each.
+    Average,
+}
+
+impl fmt::Display for PriceChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let choice = match self {
+            PriceChoice::Keep => "keep",
+            PriceChoice::Take => "take",
+            PriceChoice::Average => "average",
+        };
+        write!(f, "{choice}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Prices {
+    pub cost_price: Money,
+    pub retail_price: Money,
+}
+
+impl Prices {
+    fn of(product: &Product) -> Self {
+        Prices {
+            cost_price: product.cost_price,
+            retail_price: product.retail_price,
+        }
+    }
+}
+
+// Audit record of a merge, kept so that the products and prices before the merge can be
+// traced after the merged product is gone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeRecord {
+    pub survivor: String,
+    pub merged: String,
+    pub quantity: Quantity,
+    pub unit: Unit,
+    pub price_choice: Option<PriceChoice>,
+    pub survivor_prices: Prices,
+    pub merged_prices: Prices,
+    pub prices: Prices,
+    pub sales_moved: usize,
+    pub purchases_moved: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+fn average(a: Money, a_quantity: Quantity, b: Money, b_quantity: Quantity) -> Money {
+    let total = a_quantity + b_quantity;
+    if total.is_zero() {
+        return (a + b).per_unit(Quantity::from(2));
+    }
+    (a * a_quantity + b * b_quantity).per_unit(total)
+}
+
+// Merges the product `merged` into `survivor`: stock, cost layers, packs, tags and
+// attributes move over, the merged product's names become aliases of the survivor, and
+// every sale and purchase of the merged product is re-pointed to the survivor. When the
+// two products have different prices, `price_choice` must say which prices to keep.
+pub fn merge_products(
+    survivor: &str,
+    merged: &str,
+    price_choice: Option<PriceChoice>,
+    costing_method: CostingMethod,
+    inventory: &mut impl Inventory,
+    sales: &mut Vec<Sale>,
+    purchases: &mut Vec<Purchase>,
+) -> Result<MergeRecord, StoreError> {
+    let survivor_index = inventory
+        .position(survivor)
+        .ok_or_else(|| product_not_found(survivor, inventory))?;
+    let merged_index = inventory
+        .position(merged)
+        .ok_or_else(|| product_not_found(merged, inventory))?;
+    if survivor_index == merged_index {
+        return Err(StoreError::invalid(
+            "A product cannot be merged into itself",
+        ));
+    }
+
+    for index in [survivor_index, merged_index] {
+        let product = &inventory.products()[index];
+        if product.variant.is_some() {
+            return Err(StoreError::invalid(format!(
+                "Product {} is a variant and cannot be merged",
+                product.name
+            )));
+        }
+        if !inventory.variant_positions(&product.name).is_empty() {
+            return Err(StoreError::invalid(format!(
+                "Product {} has variants and cannot be merged",
+                product.name
+            )));
+        }
+    }
+
+    let mut source = inventory.products()[merged_index].clone();
+    source.reconcile_cost_layers(costing_method);
+    let target = &inventory.products()[survivor_index];
+    if source.unit != target.unit {
+        return Err(StoreError::invalid(format!(
+            "Product {} is measured in {} and {} in {}; only products with the same unit can be merged",
+            target.name, target.unit, source.name, source.unit
+        )));
+    }
+
+    let survivor_prices = Prices::of(target);
+    let merged_prices = Prices::of(&source);
+    let prices = match price_choice {
+        None if survivor_prices != merged_prices => {
+            return Err(StoreError::invalid(format!(
+                "Products {} and {} have different prices (cost {} and {}, retail {} and {}); choose --prices keep, take or average",
+                target.name,
+                source.name,
+                survivor_prices.cost_price,
+                merged_prices.cost_price,
+                survivor_prices.retail_price,
+                merged_prices.retail_price
+            )));
+        }
+        None | Some(PriceChoice::Keep) => survivor_prices,
+        Some(PriceChoice::Take) => merged_prices,
+        Some(PriceChoice::Average) => Prices {
+            cost_price: average(
+                target.cost_price,
+                target.quantity,
+                source.cost_price,
+                source.quantity,
+            ),
+            retail_price: average(
+                target.retail_price,
+                target.quantity,
+                source.retail_price,
+                source.quantity,
+            ),
+        },
+    };
+
+    let sales_moved = sales
+        .iter()
+        .filter(|s| source.matches(&s.product_name))
+        .count();
+    let purchases_moved = purchases
+        .iter()
+        .filter(|p| source.matches(&p.product_name))
+        .count();
+
+    inventory.update_product(survivor_index, |target| {
+        target.reconcile_cost_layers(costing_method);
+        target.quantity += source.quantity;
+        target
+            .cost_layers
+            .extend(source.cost_layers.iter().cloned());
+        target.cost_price = prices.cost_price;
+        target.retail_price = prices.retail_price;
+        for alias in std::iter::once(&source.name).chain(&source.aliases) {
+            if !target.matches(alias) {
+                target.aliases.push(alias.clone());
+            }
+        }
+        for pack in &source.packs {
+            if !target.packs.iter().any(|p| p.name == pack.name) {
+                target.packs.push(pack.clone());
+            }
+        }
+        for tag in &source.tags {
+            if !target.has_tag(tag) {
+                target.tags.push(tag.clone());
+            }
+        }
+        for (name, value) in &source.attributes {
+            target
+                .attributes
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        if target.category.is_none() {
+            target.category = source.category.clone();
+        }
+    });
+
+    inventory.take_product(merged_index);
+    let target = &inventory.products()[survivor_index - usize::from(merged_index < survivor_index)];
+    sales.relink_product(target);
+    purchases.relink_product(target);
+
+    Ok(MergeRecord {
+        survivor: target.name.clone(),
+        merged: source.name,
+        quantity: source.quantity,
+        unit: source.unit,
+        price_choice,
+        survivor_prices,
+        merged_prices,
+        prices,
+        sales_moved,
+        purchases_moved,
+        timestamp: Utc::now(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Conversion;
+    use crate::inventory::Inventory;
+
+    type Store = (Vec<Product>, Vec<Sale>, Vec<Purchase>);
+
+    fn store() -> Result<Store, StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut sales: Vec<Sale> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        let conversion = Conversion::base("USD");
+        purchases.record_purchase("Coke 330ml", 10.into(), None, None, Money::from_cents(50), Some(Money::from_cents(100)), "Can", &conversion, CostingMethod::Fifo, &mut inventory)?;
+        purchases.record_purchase("Coca Cola 330ml", 20.into(), None, None, Money::from_cents(80), Some(Money::from_cents(120)), "Can", &conversion, CostingMethod::Fifo, &mut inventory)?;
+        sales.record_sale("Coca Cola 330ml", 5.into(), None, None, &conversion, CostingMethod::Fifo, &mut inventory)?;
+        Ok((inventory, sales, purchases))
+    }
+
+    #[test]
+    fn test_merge_products() -> Result<(), StoreError> {
+        let (mut inventory, mut sales, mut purchases) = store()?;
+        let record = merge_products("Coke 330ml", "coca cola 330ml", Some(PriceChoice::Average), CostingMethod::Fifo, &mut inventory, &mut sales, &mut purchases)?;
+        assert_eq!(inventory.len(), 1);
+        let product = inventory.get_item("Coca Cola 330ml").unwrap();
+        assert_eq!(product.name, "Coke 330ml");
+        assert_eq!(product.quantity, 25.into());
+        assert_eq!(product.stock_value(), Money::from_cents(1700));
+        assert_eq!(product.cost_price, Money::from_cents(68));
+        assert_eq!(product.retail_price, Money::from_cents(112));
+        assert!(sales.iter().all(|s| s.product_name == "Coke 330ml"));
+        assert!(purchases.iter().all(|p| p.product_name == "Coke 330ml"));
+        assert_eq!((record.sales_moved, record.purchases_moved), (1, 1));
+        assert_eq!(record.merged_prices.retail_price, Money::from_cents(120));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_requires_price_choice() -> Result<(), StoreError> {
+        let (mut inventory, mut sales, mut purchases) = store()?;
+        assert!(merge_products("Coke 330ml", "Coca Cola 330ml", None, CostingMethod::Fifo, &mut inventory, &mut sales, &mut purchases).is_err());
+        assert!(merge_products("Coke 330ml", "Coke 330ml", Some(PriceChoice::Keep), CostingMethod::Fifo, &mut inventory, &mut sales, &mut purchases).is_err());
+        assert_eq!(inventory.len(), 2);
+        merge_products("Coke 330ml", "Coca Cola 330ml", Some(PriceChoice::Keep), CostingMethod::Fifo, &mut inventory, &mut sales, &mut purchases)?;
+        assert_eq!(inventory.get_item("Coke 330ml").unwrap().retail_price, Money::from_cents(100));
+        Ok(())
+    }
+}
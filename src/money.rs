@@ -0,0 +1,226 @@
+use crate::units::Quantity;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Sub};
+use std::str::FromStr;
+use validator::ValidationError;
+
+// Rounding rules:
+// - unit prices and unit costs keep up to 4 decimal places, rounded half to even;
+// - amounts (line totals, cost of goods, profit) are rounded to cents, half away from zero;
+// - reports print amounts in cents.
+const UNIT_SCALE: u32 = 4;
+const CENT_SCALE: u32 = 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Money(Decimal);
+
+impl Money {
+    pub const ZERO: Money = Money(Decimal::ZERO);
+
+    #[cfg(test)]
+    pub fn from_cents(cents: i64) -> Self {
+        Money(Decimal::new(cents, CENT_SCALE))
+    }
+
+    pub fn from_decimal(value: Decimal) -> Self {
+        Money(value).round_unit()
+    }
+
+    pub fn round_unit(self) -> Self {
+        Money(
+            self.0
+                .round_dp_with_strategy(UNIT_SCALE, RoundingStrategy::MidpointNearestEven)
+                .normalize(),
+        )
+    }
+
+    pub fn round_cents(self) -> Self {
+        Money(
+            self.0
+                .round_dp_with_strategy(CENT_SCALE, RoundingStrategy::MidpointAwayFromZero),
+        )
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.is_sign_negative() && !self.0.is_zero()
+    }
+
+    // Share of `total` as a percentage, rounded to one decimal place.
+    pub fn percent_of(self, total: Money) -> Decimal {
+        if total.0.is_zero() {
+            return Decimal::ZERO;
+        }
+        (self.0 / total.0 * Decimal::ONE_HUNDRED)
+            .round_dp_with_strategy(1, RoundingStrategy::MidpointAwayFromZero)
+    }
+
+    // Price of `quantity` units, rounded to cents.
+    pub fn times(self, quantity: Quantity) -> Self {
+        (self * quantity).round_cents()
+    }
+
+    // Amount in another currency given the exchange rate, at unit precision.
+    pub fn convert(self, rate: Decimal) -> Self {
+        Money(self.0 * rate).round_unit()
+    }
+
+    // Price of a single unit out of `quantity`, at unit precision.
+    pub fn per_unit(self, quantity: Quantity) -> Self {
+        if quantity.is_zero() {
+            return Money::ZERO;
+        }
+        Money(self.0 / quantity.as_decimal()).round_unit()
+    }
+}
+
+pub fn validate_non_negative(value: &Money) -> Result<(), ValidationError> {
+    if value.is_negative() {
+        return Err(ValidationError::new("range"));
+    }
+    Ok(())
+}
+
+pub fn validate_positive(value: &Money) -> Result<(), ValidationError> {
+    if value.is_negative() || *value == Money::ZERO {
+        return Err(ValidationError::new("range"));
+    }
+    Ok(())
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2}", self.round_cents().0)
+    }
+}
+
+impl FromStr for Money {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = Decimal::from_str(s.trim()).map_err(|_| format!("Invalid amount: {s}"))?;
+        if value.scale() > UNIT_SCALE {
+            return Err(format!(
+                "Amount {s} has more than {UNIT_SCALE} decimal places"
+            ));
+        }
+        Ok(Money(value.normalize()))
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money(self.0 - other.0)
+    }
+}
+
+impl Mul<Quantity> for Money {
+    type Output = Money;
+
+    fn mul(self, quantity: Quantity) -> Money {
+        Money(self.0 * quantity.as_decimal())
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
+
+// Amounts are written as JSON strings so no precision is lost on a round trip.
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.to_string())
+    }
+}
+
+// Data files written before `Money` existed hold amounts as JSON floats. Those are read
+// through their shortest decimal representation and rounded to unit precision, so 0.75
+// becomes exactly 0.75 rather than the nearest binary fraction.
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a decimal amount as a string or number")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Money, E> {
+                Decimal::from_str(value)
+                    .map(Money::from_decimal)
+                    .map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Money, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Money, E> {
+                Ok(Money(Decimal::from(value)))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Money, E> {
+                Ok(Money(Decimal::from(value)))
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money_from_legacy_float() {
+        let money: Money = serde_json::from_str("0.30000000000000004").unwrap();
+        assert_eq!(money, Money::from_cents(30));
+        assert_eq!(serde_json::to_string(&money).unwrap(), "\"0.3\"");
+    }
+
+    #[test]
+    fn test_money_rounding() {
+        let unit: Money = "0.3333".parse().unwrap();
+        assert_eq!(unit.times(Quantity::from(3)), Money::from_cents(100));
+        assert_eq!(Money::from_cents(100).per_unit(Quantity::from(3)), unit);
+        assert_eq!("0.005".parse::<Money>().unwrap().round_cents(), Money::from_cents(1));
+        assert!("0.00001".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn test_money_sum_is_exact() {
+        let total: Money = std::iter::repeat_n(Money::from_cents(10), 1000).sum();
+        assert_eq!(total, Money::from_cents(10000));
+        assert_eq!(total.to_string(), "100.00");
+    }
+}
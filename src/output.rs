@@ -0,0 +1,168 @@
+use crate::error::StoreError;
+use crate::report::PlainRecord;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+
+// How commands print their results. Table is the readable default; JSON gives each
+// command a stable schema for scripts; plain prints bare values and tab-separated
+// records without headings or decoration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Plain,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Output {
+    pub format: OutputFormat,
+}
+
+// What a command printed while its output was captured: the text of table and plain
+// output, or the documents of JSON output.
+#[derive(Debug, Default)]
+pub struct Captured {
+    pub text: String,
+    pub values: Vec<Value>,
+}
+
+impl Captured {
+    // The captured JSON as one value: null when nothing was printed, the document itself
+    // when one was, and an array of them otherwise.
+    pub fn into_json(mut self) -> Value {
+        match self.values.len() {
+            0 => Value::Null,
+            1 => self.values.remove(0),
+            _ => Value::Array(self.values),
+        }
+    }
+}
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Captured>> = const { RefCell::new(None) };
+}
+
+impl Output {
+    pub fn new(format: OutputFormat) -> Self {
+        Output { format }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    // Runs `f` with what it prints to stdout collected instead of printed, so that it can
+    // be combined with other results or dropped. Captures can be nested.
+    pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Captured) {
+        let outer = CAPTURE.replace(Some(Captured::default()));
+        let result = f();
+        let captured = CAPTURE.replace(outer).unwrap_or_default();
+        (result, captured)
+    }
+
+    // Prints text to stdout as it is, or adds it to the capture in progress.
+    pub fn print(&self, text: &str) {
+        CAPTURE.with_borrow_mut(|capture| match capture {
+            Some(captured) => captured.text.push_str(text),
+            None => print!("{text}"),
+        })
+    }
+
+    pub fn line(&self, text: impl fmt::Display) {
+        self.print(&format!("{text}\n"));
+    }
+
+    // Confirms a change: a line of text, or in JSON the record the change produced.
+    pub fn done(&self, message: &str, record: &impl Serialize) -> Result<(), StoreError> {
+        match self.format {
+            OutputFormat::Table => self.line(format_args!("✓ {message}")),
+            OutputFormat::Plain => self.line(message),
+            OutputFormat::Json => self.json(record)?,
+        }
+        Ok(())
+    }
+
+    // Confirms a new record, such as a sale: plain output is the record itself.
+    pub fn recorded<R: Serialize + PlainRecord>(
+        &self,
+        message: &str,
+        record: &R,
+    ) -> Result<(), StoreError> {
+        match self.format {
+            OutputFormat::Plain => self.line(record.fields().join("\t")),
+            _ => self.done(message, record)?,
+        }
+        Ok(())
+    }
+
+    // A line of detail under a confirmation, in table output only; JSON output carries
+    // the details in the record.
+    pub fn detail(&self, label: &str, value: impl fmt::Display) {
+        if self.format == OutputFormat::Table {
+            self.line(format_args!("  {label}: {value}"));
+        }
+    }
+
+    // Labelled values, aligned in table output and tab-separated in plain output.
+    pub fn fields(&self, fields: &[(&str, String)]) {
+        let width = fields
+            .iter()
+            .map(|(label, _)| label.chars().count() + 1)
+            .max()
+            .unwrap_or_default();
+        for (label, value) in fields {
+            match self.format {
+                OutputFormat::Table => {
+                    self.line(format_args!("{:<width$} {value}", format!("{label}:")))
+                }
+                _ => self.line(format_args!("{label}\t{value}")),
+            }
+        }
+    }
+
+    // Advice for the user rather than a result. It goes to stderr except in table
+    // output, so that JSON and plain output stay parseable.
+    pub fn note(&self, message: &str) {
+        match self.format {
+            OutputFormat::Table => self.line(format_args!("Note: {message}")),
+            OutputFormat::Json | OutputFormat::Plain => eprintln!("Note: {message}"),
+        }
+    }
+
+    pub fn json(&self, value: &impl Serialize) -> Result<(), StoreError> {
+        let value = serde_json::to_value(value)?;
+        let printed = CAPTURE.with_borrow_mut(|capture| match capture {
+            Some(captured) => {
+                captured.values.push(value);
+                None
+            }
+            None => Some(value),
+        });
+        if let Some(value) = printed {
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        }
+        Ok(())
+    }
+
+    // In JSON the error is an object under "error", printed to stdout like any other
+    // result; see StoreError's Serialize impl for its fields.
+    pub fn error(&self, error: &StoreError) {
+        match self.format {
+            OutputFormat::Table => self.line(format_args!("⛌ {error}")),
+            OutputFormat::Plain => eprintln!("{error}"),
+            OutputFormat::Json => {
+                #[derive(Serialize)]
+                struct ErrorObject<'a> {
+                    error: &'a StoreError,
+                }
+                if self.json(&ErrorObject { error }).is_err() {
+                    self.line(format_args!("⛌ {error}"));
+                }
+            }
+        }
+    }
+}
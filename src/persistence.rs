@@ -1,23 +1,41 @@
+use crate::category::Category;
+use crate::costing::{layers_from_purchases, layers_quantity};
+use crate::currency::ExchangeRate;
+use crate::error::StoreError;
 use crate::inventory::Product;
+use crate::merge::MergeRecord;
+use crate::money::Money;
 use crate::purchase::Purchase;
 use crate::sales::Sale;
+use crate::settings::StoreSettings;
+use crate::units::Quantity;
 use serde::{Deserialize, Serialize};
-use serde_json::from_str;
+use serde_json::{Value, from_str};
+use std::fmt;
 use std::fs;
 
 const DATA_DIR: &str = "data";
 const INVENTORY_FILE: &str = "data/inventory.json";
 const SALES_FILE: &str = "data/sales.json";
 const PURCHASES_FILE: &str = "data/purchases.json";
+const SETTINGS_FILE: &str = "data/settings.json";
+const EXCHANGE_RATES_FILE: &str = "data/exchange_rates.json";
+const CATEGORIES_FILE: &str = "data/categories.json";
+const MERGES_FILE: &str = "data/merges.json";
 
-fn ensure_data_dir() -> Result<(), Box<dyn std::error::Error>> {
+// Names the file in storage errors, as I/O and JSON errors do not.
+fn storage_error(filename: &str, error: impl fmt::Display) -> StoreError {
+    StoreError::Storage(format!("{filename}: {error}"))
+}
+
+fn ensure_data_dir() -> Result<(), StoreError> {
     if !std::path::Path::new(DATA_DIR).exists() {
         fs::create_dir(DATA_DIR)?;
     }
     Ok(())
 }
 
-fn load_data<T>(filename: &str) -> Result<Vec<T>, Box<dyn std::error::Error>>
+fn load_data<T>(filename: &str) -> Result<Vec<T>, StoreError>
 where
     T: for<'de> Deserialize<'de>,
 {
@@ -26,44 +44,268 @@ where
     if !std::path::Path::new(filename).exists() {
         return Ok(Vec::new());
     }
-    let json = fs::read_to_string(filename)?;
-    let data: Vec<T> = from_str(&json)?;
+    let json = fs::read_to_string(filename).map_err(|e| storage_error(filename, e))?;
+    let data: Vec<T> = from_str(&json).map_err(|e| storage_error(filename, e))?;
     Ok(data)
 }
 
 // Generic save function
-fn save_data<T>(data: &Vec<T>, filename: &str) -> Result<(), Box<dyn std::error::Error>>
+fn save_data<T>(data: &[T], filename: &str) -> Result<(), StoreError>
 where
     T: Serialize,
 {
     ensure_data_dir()?;
 
     let json = serde_json::to_string_pretty(data)?;
-    fs::write(filename, json)?;
+    fs::write(filename, json).map_err(|e| storage_error(filename, e))?;
     Ok(())
 }
 
+// Products saved before cost and retail prices were split carry a single `price`. It was
+// entered as the retail price, and stands in for the cost price until a purchase shows
+// what the product actually cost. Returns whether the cost price is such a stand-in.
+fn migrate_legacy_price(product: &mut Value) -> bool {
+    let Some(fields) = product.as_object_mut() else {
+        return false;
+    };
+    let Some(price) = fields.remove("price") else {
+        return false;
+    };
+    let has_cost_price = fields.contains_key("cost_price");
+    fields.entry("retail_price").or_insert(price.clone());
+    fields.entry("cost_price").or_insert(price);
+    !has_cost_price
+}
+
+// Migrates saved products and fills in what older files lack: the cost price of legacy
+// products from their most recent purchase, and cost layers from purchase history.
+// Purchases are matched under any name the product is known by, as in recompute_profits.
+fn migrate_inventory(
+    products: Vec<Value>,
+    purchases: &[Purchase],
+) -> Result<Vec<Product>, serde_json::Error> {
+    let mut inventory = Vec::new();
+    for mut value in products {
+        let legacy_price = migrate_legacy_price(&mut value);
+        let mut product: Product = serde_json::from_value(value)?;
+        if legacy_price
+            && let Some(purchase) = purchases
+                .iter()
+                .rev()
+                .find(|p| product.matches(&p.product_name))
+        {
+            product.cost_price = purchase.base_purchase_price;
+        }
+        if layers_quantity(&product.cost_layers) != product.quantity {
+            product.cost_layers = layers_from_purchases(&product, purchases);
+        }
+        inventory.push(product);
+    }
+    Ok(inventory)
+}
+
+// Sales saved before cost of goods was stored only kept the profit, so the cost that
+// produced it is whatever separates the profit from the sale total.
+fn migrate_legacy_sale_cost(sale: &mut Value) {
+    let Some(fields) = sale.as_object_mut() else {
+        return;
+    };
+    if fields.contains_key("cost_of_goods") {
+        return;
+    }
+    let amount = |key: &str| {
+        fields
+            .get(key)
+            .and_then(|value| Money::deserialize(value).ok())
+            .unwrap_or_default()
+    };
+    let quantity = fields
+        .get("quantity")
+        .and_then(|value| Quantity::deserialize(value).ok())
+        .unwrap_or_default();
+    let cost_of_goods = amount("total") - amount("profit");
+    let unit_cost = cost_of_goods.per_unit(quantity);
+    fields.insert(
+        "cost_of_goods".to_string(),
+        Value::from(cost_of_goods.to_string()),
+    );
+    fields.insert("unit_cost".to_string(), Value::from(unit_cost.to_string()));
+}
+
+// Sales and purchases saved before multi-currency support were all in the store's base
+// currency, so their base amounts are the recorded amounts at a rate of one.
+fn migrate_legacy_currency(record: &mut Value, base_currency: &str, amounts: &[(&str, &str)]) {
+    let Some(fields) = record.as_object_mut() else {
+        return;
+    };
+    if fields.contains_key("currency") {
+        return;
+    }
+    fields.insert("currency".to_string(), Value::from(base_currency));
+    fields.insert("exchange_rate".to_string(), Value::from("1"));
+    for (amount, base_amount) in amounts {
+        if let Some(value) = fields.get(*amount).cloned() {
+            fields.insert(base_amount.to_string(), value);
+        }
+    }
+}
+
 // Public API functions using the generic implementations
-pub fn load_inventory() -> Result<Vec<Product>, Box<dyn std::error::Error>> {
-    load_data(INVENTORY_FILE)
+pub fn load_inventory() -> Result<Vec<Product>, StoreError> {
+    let products: Vec<Value> = load_data(INVENTORY_FILE)?;
+    let purchases = load_purchases()?;
+    migrate_inventory(products, &purchases).map_err(|e| storage_error(INVENTORY_FILE, e))
+}
+
+pub fn load_sales() -> Result<Vec<Sale>, StoreError> {
+    let mut sales: Vec<Value> = load_data(SALES_FILE)?;
+    let settings = load_settings()?;
+    for sale in &mut sales {
+        migrate_legacy_sale_cost(sale);
+        migrate_legacy_currency(sale, &settings.base_currency, &[("total", "base_total")]);
+    }
+    let sales = sales
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()
+        .map_err(|e| storage_error(SALES_FILE, e))?;
+    Ok(sales)
+}
+
+pub fn load_purchases() -> Result<Vec<Purchase>, StoreError> {
+    let mut purchases: Vec<Value> = load_data(PURCHASES_FILE)?;
+    let settings = load_settings()?;
+    for purchase in &mut purchases {
+        migrate_legacy_currency(
+            purchase,
+            &settings.base_currency,
+            &[
+                ("purchase_price", "base_purchase_price"),
+                ("total_cost", "base_total_cost"),
+            ],
+        );
+    }
+    let purchases = purchases
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()
+        .map_err(|e| storage_error(PURCHASES_FILE, e))?;
+    Ok(purchases)
+}
+
+pub fn load_exchange_rates() -> Result<Vec<ExchangeRate>, StoreError> {
+    load_data(EXCHANGE_RATES_FILE)
 }
 
-pub fn load_sales() -> Result<Vec<Sale>, Box<dyn std::error::Error>> {
-    load_data(SALES_FILE)
+pub fn load_categories() -> Result<Vec<Category>, StoreError> {
+    load_data(CATEGORIES_FILE)
 }
 
-pub fn load_purchases() -> Result<Vec<Purchase>, Box<dyn std::error::Error>> {
-    load_data(PURCHASES_FILE)
+pub fn load_merges() -> Result<Vec<MergeRecord>, StoreError> {
+    load_data(MERGES_FILE)
 }
 
-pub fn save_inventory(inventory: &Vec<Product>) -> Result<(), Box<dyn std::error::Error>> {
+pub fn load_settings() -> Result<StoreSettings, StoreError> {
+    ensure_data_dir()?;
+
+    if !std::path::Path::new(SETTINGS_FILE).exists() {
+        return Ok(StoreSettings::default());
+    }
+    let json = fs::read_to_string(SETTINGS_FILE).map_err(|e| storage_error(SETTINGS_FILE, e))?;
+    from_str(&json).map_err(|e| storage_error(SETTINGS_FILE, e))
+}
+
+pub fn save_inventory(inventory: &[Product]) -> Result<(), StoreError> {
     save_data(inventory, INVENTORY_FILE)
 }
 
-pub fn save_sales(sales: &Vec<Sale>) -> Result<(), Box<dyn std::error::Error>> {
+pub fn save_sales(sales: &[Sale]) -> Result<(), StoreError> {
     save_data(sales, SALES_FILE)
 }
 
-pub fn save_purchases(purchases: &Vec<Purchase>) -> Result<(), Box<dyn std::error::Error>> {
+pub fn save_purchases(purchases: &[Purchase]) -> Result<(), StoreError> {
     save_data(purchases, PURCHASES_FILE)
 }
+
+pub fn save_categories(categories: &[Category]) -> Result<(), StoreError> {
+    save_data(categories, CATEGORIES_FILE)
+}
+
+pub fn save_merges(merges: &[MergeRecord]) -> Result<(), StoreError> {
+    save_data(merges, MERGES_FILE)
+}
+
+pub fn save_settings(settings: &StoreSettings) -> Result<(), StoreError> {
+    ensure_data_dir()?;
+
+    let json = serde_json::to_string_pretty(settings)?;
+    fs::write(SETTINGS_FILE, json).map_err(|e| storage_error(SETTINGS_FILE, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Unit;
+    use serde_json::json;
+
+    #[test]
+    fn test_migrate_legacy_price() {
+        let product = json!({
+            "name": "Test Product",
+            "price": 10.0,
+            "quantity": 5,
+            "description": "Test description",
+            "aliases": ["Old Product"]
+        });
+        let purchases = vec![Purchase {
+            product_name: "old product".to_string(),
+            quantity: 5.into(),
+            unit: Unit::Each,
+            purchase_price: Money::from_cents(600),
+            total_cost: Money::from_cents(3000),
+            currency: "USD".to_string(),
+            exchange_rate: rust_decimal::Decimal::ONE,
+            base_purchase_price: Money::from_cents(600),
+            base_total_cost: Money::from_cents(3000),
+            timestamp: chrono::Utc::now(),
+            pack: None,
+        }];
+        let inventory = migrate_inventory(vec![product], &purchases).unwrap();
+        assert_eq!(inventory[0].cost_price, Money::from_cents(600));
+        assert_eq!(inventory[0].retail_price, Money::from_cents(1000));
+        assert_eq!(inventory[0].cost_layers.len(), 1);
+    }
+
+    #[test]
+    fn test_migrate_legacy_sale_cost() {
+        let mut sale = json!({
+            "product_name": "Test Product",
+            "quantity": 5,
+            "sale_price": 10.0,
+            "profit": 20.0,
+            "total": 50.0,
+            "timestamp": "2025-07-31T14:39:43Z"
+        });
+        migrate_legacy_sale_cost(&mut sale);
+        migrate_legacy_currency(&mut sale, "USD", &[("total", "base_total")]);
+        let sale: Sale = serde_json::from_value(sale).unwrap();
+        assert_eq!(sale.cost_of_goods, Money::from_cents(3000));
+        assert_eq!(sale.unit_cost, Money::from_cents(600));
+    }
+
+    #[test]
+    fn test_migrate_legacy_currency() {
+        let mut purchase = json!({
+            "product_name": "Test Product",
+            "quantity": 5,
+            "purchase_price": "6.00",
+            "total_cost": "30.00",
+            "timestamp": "2025-07-31T14:39:43Z"
+        });
+        migrate_legacy_currency(&mut purchase, "USD", &[("purchase_price", "base_purchase_price"), ("total_cost", "base_total_cost")]);
+        let purchase: Purchase = serde_json::from_value(purchase).unwrap();
+        assert_eq!(purchase.currency, "USD");
+        assert_eq!(purchase.base_total_cost, Money::from_cents(3000));
+    }
+}
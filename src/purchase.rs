@@ -1,116 +1,347 @@
-use crate::inventory::{Inventory, Product};
+use crate::costing::{CostLayer, CostingMethod};
+use crate::currency::Conversion;
+use crate::error::{Entity, StoreError};
+use crate::inventory::{Inventory, Product, ProductStatus, display_name};
+use crate::money::{Money, validate_positive};
+use crate::units::{Quantity, Unit, validate_positive_quantity};
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use validator::Validate;
 
 #[derive(Validate, Clone, Debug, Serialize, Deserialize)]
 pub struct Purchase {
     #[validate(length(min = 1, message = "Product name cannot be empty"))]
     pub product_name: String,
-    #[validate(range(min = 1, message = "Quantity must be at least 1"))]
-    pub quantity: u32,
-    #[validate(range(min = 0.01, message = "Purchase price must be greater than 0"))]
-    pub purchase_price: f64,
-    pub total_cost: f64,
+    #[validate(custom(
+        function = "validate_positive_quantity",
+        message = "Quantity must be greater than 0"
+    ))]
+    pub quantity: Quantity,
+    #[serde(default)]
+    pub unit: Unit,
+    #[validate(custom(
+        function = "validate_positive",
+        message = "Purchase price must be greater than 0"
+    ))]
+    pub purchase_price: Money,
+    pub total_cost: Money,
+    pub currency: String,
+    pub exchange_rate: Decimal,
+    pub base_purchase_price: Money,
+    pub base_total_cost: Money,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub pack: Option<PackPurchase>,
+}
+
+// The purchase as the supplier invoiced it, in packs at a price per pack.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PackPurchase {
+    pub pack: String,
+    pub quantity: Quantity,
+    pub price: Money,
 }
 
 pub trait Purchases {
+    #[allow(clippy::too_many_arguments)]
     fn record_purchase(
         &mut self,
         product_name: &str,
-        quantity: u32,
-        purchase_price: f64,
+        quantity: Quantity,
+        unit: Option<Unit>,
+        pack: Option<&str>,
+        purchase_price: Money,
+        retail_price: Option<Money>,
         description: &str,
-        inventory: &mut Vec<Product>,
-    ) -> Result<Purchase, String>;
+        conversion: &Conversion,
+        costing_method: CostingMethod,
+        inventory: &mut impl Inventory,
+    ) -> Result<Purchase, StoreError>;
+    fn relink_product(&mut self, product: &Product);
 }
 
 impl Purchases for Vec<Purchase> {
+    // `quantity` and `purchase_price` are in `unit`, which defaults to the product's own
+    // unit, or in one of the product's packs. The purchase is recorded in the product's
+    // unit; a new product takes on `unit`.
+    #[allow(clippy::too_many_arguments)]
     fn record_purchase(
         &mut self,
         product_name: &str,
-        quantity: u32,
-        purchase_price: f64,
+        quantity: Quantity,
+        unit: Option<Unit>,
+        pack: Option<&str>,
+        purchase_price: Money,
+        retail_price: Option<Money>,
         description: &str,
-        inventory: &mut Vec<Product>,
-    ) -> Result<Purchase, String> {
+        conversion: &Conversion,
+        costing_method: CostingMethod,
+        inventory: &mut impl Inventory,
+    ) -> Result<Purchase, StoreError> {
+        let existing = inventory.get_item(product_name);
+        if let Some(product) = existing.filter(|p| p.is_archived()) {
+            return Err(StoreError::invalid(format!(
+                "Product {} is archived and cannot be purchased; set its status to active first",
+                product.name
+            )));
+        }
+        // Stock of a product with variants is held by the variants, as they are what is sold.
+        if let Some(product) = existing {
+            let skus: Vec<&str> = inventory
+                .variants_of(&product.name)
+                .into_iter()
+                .filter_map(|p| p.variant.as_ref().map(|v| v.sku.as_str()))
+                .collect();
+            if !skus.is_empty() {
+                return Err(StoreError::invalid(format!(
+                    "Product {} has variants; receive one of them: {}",
+                    product.name,
+                    skus.join(", ")
+                )));
+            }
+        }
+        let product_name = existing.map_or_else(|| display_name(product_name), |p| p.name.clone());
+        let product_unit = existing.map_or(unit.unwrap_or_default(), |p| p.unit);
+        let total_cost = purchase_price.times(quantity);
+        let (quantity, purchase_price, pack) = match pack {
+            Some(pack) => {
+                if unit.is_some() {
+                    return Err(StoreError::invalid(
+                        "A purchase is either in a unit or in a pack, not both",
+                    ));
+                }
+                let size = existing
+                    .ok_or_else(|| StoreError::not_on_product(&product_name, Entity::Pack, pack))?
+                    .pack_size(pack)?;
+                let base_quantity = quantity * size;
+                base_quantity.check_precision(product_unit)?;
+                let pack = PackPurchase {
+                    pack: pack.to_string(),
+                    quantity,
+                    price: purchase_price,
+                };
+                (base_quantity, purchase_price.per_unit(size), Some(pack))
+            }
+            None => {
+                let unit = unit.unwrap_or(product_unit);
+                (
+                    unit.convert(quantity, product_unit)?,
+                    purchase_price.convert(unit.ratio_to(product_unit)?),
+                    None,
+                )
+            }
+        };
+        let base_purchase_price = conversion.to_base(purchase_price);
+        // The retail price is given in the invoice's currency too, but the product is priced
+        // in the base currency.
+        let retail_price = retail_price.map(|price| conversion.to_base(price).round_cents());
         let purchase = Purchase {
-            product_name: product_name.to_string(),
+            product_name: product_name.clone(),
             quantity,
+            unit: product_unit,
             purchase_price,
+            total_cost,
+            currency: conversion.currency.clone(),
+            exchange_rate: conversion.rate,
+            base_purchase_price,
+            base_total_cost: conversion.to_base(total_cost).round_cents(),
             timestamp: Utc::now(),
-            total_cost: purchase_price * quantity as f64,
+            pack,
         };
-        purchase
-            .validate()
-            .map_err(|errors| format!("Validation errors: {errors:#?}"))?;
+        purchase.validate()?;
 
-        match inventory.iter_mut().find(|p| p.name == product_name) {
-            Some(product) => {
+        match inventory.position(&product_name) {
+            Some(index) => inventory.update_product(index, |product| {
+                product.reconcile_cost_layers(costing_method);
                 product.quantity += quantity;
-            }
+                product.cost_price = base_purchase_price;
+                product.cost_layers.push(CostLayer {
+                    quantity,
+                    unit_cost: base_purchase_price,
+                });
+                if let Some(retail_price) = retail_price {
+                    product.retail_price = retail_price;
+                }
+            }),
             None => {
+                let retail_price = retail_price.ok_or_else(|| {
+                    StoreError::invalid_field(
+                        "retail_price",
+                        format!("Retail price is required for new product {product_name}"),
+                    )
+                })?;
                 let new_product = Product {
-                    name: product_name.to_string(),
-                    price: purchase_price,
+                    name: product_name,
+                    cost_price: base_purchase_price,
+                    retail_price,
                     quantity,
+                    unit: product_unit,
+                    packs: Vec::new(),
                     description: description.to_string(),
+                    aliases: Vec::new(),
+                    status: ProductStatus::Active,
+                    category: None,
+                    attributes: BTreeMap::new(),
+                    tags: Vec::new(),
+                    variant: None,
+                    cost_layers: vec![CostLayer {
+                        quantity,
+                        unit_cost: base_purchase_price,
+                    }],
                 };
 
-                new_product
-                    .validate()
-                    .map_err(|errors| format!("Validation errors: {errors:#?}"))?;
+                new_product.validate()?;
 
-                inventory.push(new_product);
+                inventory.insert_product(new_product);
             }
         }
         self.push(purchase.clone());
         Ok(purchase)
     }
+
+    fn relink_product(&mut self, product: &Product) {
+        self.iter_mut()
+            .filter(|p| product.matches(&p.product_name))
+            .for_each(|p| p.product_name = product.name.clone());
+    }
 }
 
+#[cfg(test)]
 mod tests {
+    use crate::inventory::Inventory;
     use super::*;
 
     #[test]
-    fn test_record_purchase()-> Result<(), String> {
+    fn test_record_purchase()-> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 5.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_invalid_product_name()-> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        assert!(purchases.record_purchase("", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_invalid_quantity()-> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        let error = purchases.record_purchase("Test Product", 0.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).unwrap_err();
+        assert_eq!(error, StoreError::invalid_field("quantity", "Quantity must be greater than 0"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_invalid_price()-> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        assert!(purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(0), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_invalid_description()-> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        assert!(purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_by_alias() -> Result<(), StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        inventory.rename_item("Test Product", "Renamed Product")?;
+        let purchase = purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(purchase.product_name, "Renamed Product");
+        assert_eq!(inventory.get_item("Renamed Product").unwrap().quantity, 10.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_new_product_requires_retail_price() {
+        let mut inventory: Vec<Product> = Vec::new();
+        let mut purchases: Vec<Purchase> = Vec::new();
+        assert!(purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        assert!(inventory.get_item("Test Product").is_none());
+        assert!(purchases.is_empty());
+    }
+
+    #[test]
+    fn test_record_purchase_updates_cost_price() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
         let mut purchases: Vec<Purchase> = Vec::new();
-        purchases.record_purchase("Test Product", 5, 10.0, "Test description", &mut inventory)?;
-        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 5);
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1200), None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        let product = inventory.get_item("Test Product").unwrap();
+        assert_eq!(product.cost_price, Money::from_cents(1200));
+        assert_eq!(product.retail_price, Money::from_cents(1500));
         Ok(())
     }
 
     #[test]
-    fn test_record_purchase_invalid_product_name()-> Result<(), String> {
+    fn test_record_purchase_archived_product() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
         let mut purchases: Vec<Purchase> = Vec::new();
-        assert!(purchases.record_purchase("", 5, 10.0, "Test description", &mut inventory).is_err());
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        inventory.set_status("Test Product", ProductStatus::Archived)?;
+        let error = purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).unwrap_err();
+        assert_eq!(error.exit_code(), 5);
+        assert_eq!((purchases.len(), inventory.get_item("Test Product").unwrap().quantity), (1, 5.into()));
         Ok(())
     }
 
     #[test]
-    fn test_record_purchase_invalid_quantity()-> Result<(), String> {
+    fn test_record_purchase_in_foreign_currency() -> Result<(), StoreError> {
         let mut inventory: Vec<Product> = Vec::new();
         let mut purchases: Vec<Purchase> = Vec::new();
-        assert!(purchases.record_purchase("Test Product", 0, 10.0, "Test description", &mut inventory).is_err());
+        let conversion = Conversion { currency: "EUR".to_string(), rate: Decimal::new(120, 2) };
+        let purchase = purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(1500)), "Test description", &conversion, CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(purchase.total_cost, Money::from_cents(5000));
+        assert_eq!(purchase.base_total_cost, Money::from_cents(6000));
+        assert_eq!(inventory.get_item("Test Product").unwrap().cost_price, Money::from_cents(1200));
+        assert_eq!(inventory.get_item("Test Product").unwrap().retail_price, Money::from_cents(1800));
+        purchases.record_purchase("Test Product", 1.into(), None, None, Money::from_cents(1000), Some(Money::from_cents(200)), "Test description", &conversion, CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(inventory.get_item("Test Product").unwrap().retail_price, Money::from_cents(240));
         Ok(())
     }
 
     #[test]
-    fn test_record_purchase_invalid_price()-> Result<(), String> {
+    fn test_record_purchase_in_another_unit() -> Result<(), Box<dyn std::error::Error>> {
         let mut inventory: Vec<Product> = Vec::new();
         let mut purchases: Vec<Purchase> = Vec::new();
-        assert!(purchases.record_purchase("Test Product", 5, 0.0, "Test description", &mut inventory).is_err());
+        purchases.record_purchase("Test Product", 2.into(), Some(Unit::Kg), None, Money::from_cents(400), Some(Money::from_cents(1000)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        let purchase = purchases.record_purchase("Test Product", 500.into(), Some(Unit::G), None, "0.005".parse()?, None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(purchase.quantity, "0.5".parse()?);
+        assert_eq!(purchase.purchase_price, Money::from_cents(500));
+        assert_eq!(purchase.total_cost, Money::from_cents(250));
+        let product = inventory.get_item("Test Product").unwrap();
+        assert_eq!(product.unit, Unit::Kg);
+        assert_eq!(product.quantity, "2.5".parse()?);
         Ok(())
     }
 
     #[test]
-    fn test_record_purchase_invalid_description()-> Result<(), String> {
+    fn test_record_purchase_in_packs() -> Result<(), Box<dyn std::error::Error>> {
         let mut inventory: Vec<Product> = Vec::new();
         let mut purchases: Vec<Purchase> = Vec::new();
-        assert!(purchases.record_purchase("Test Product", 5, 10.0, "", &mut inventory).is_err());
+        purchases.record_purchase("Test Product", 24.into(), None, None, Money::from_cents(50), Some(Money::from_cents(100)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        inventory.define_pack("Test Product", "case", 24.into(), None)?;
+        let purchase = purchases.record_purchase("Test Product", 3.into(), None, Some("case"), Money::from_cents(1000), None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(purchase.quantity, 72.into());
+        assert_eq!(purchase.purchase_price, "0.4167".parse()?);
+        assert_eq!(purchase.total_cost, Money::from_cents(3000));
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 96.into());
+        assert!(purchases.record_purchase("Test Product", 1.into(), None, Some("pallet"), Money::from_cents(1000), None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
         Ok(())
     }
 }
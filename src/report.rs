@@ -1,34 +1,301 @@
-use crate::inventory::Product;
+use crate::category::CategoryLookup;
+use crate::inventory::{Product, ProductStatus};
+use crate::money::Money;
 use crate::purchase::Purchase;
 use crate::sales::Sale;
+use crate::search::NearDuplicate;
+use crate::units::{Quantity, Unit};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+const UNCATEGORIZED: &str = "Uncategorized";
+
+// A product with its variants, which roll up into the product's line in the inventory
+// report.
+struct Family<'a> {
+    product: &'a Product,
+    variants: Vec<&'a Product>,
+}
+
+impl<'a> Family<'a> {
+    fn members(&self) -> impl Iterator<Item = &'a Product> + '_ {
+        std::iter::once(self.product).chain(self.variants.iter().copied())
+    }
+}
+
+// A product as it appears in a product listing; this is also the JSON form of a listing
+// row, so fields should only ever be added.
+#[derive(Debug, Serialize)]
+pub struct ProductRow<'a> {
+    pub name: &'a str,
+    pub sku: Option<&'a str>,
+    pub category: Option<&'a str>,
+    pub status: ProductStatus,
+    pub quantity: Quantity,
+    pub unit: Unit,
+    pub cost_price: Money,
+    pub retail_price: Money,
+    pub stock_value: Money,
+    pub tags: &'a [String],
+}
+
+impl<'a> From<&'a Product> for ProductRow<'a> {
+    fn from(product: &'a Product) -> Self {
+        ProductRow {
+            name: &product.name,
+            sku: product.variant.as_ref().map(|v| v.sku.as_str()),
+            category: product.category.as_deref(),
+            status: product.status,
+            quantity: product.quantity,
+            unit: product.unit,
+            cost_price: product.cost_price,
+            retail_price: product.retail_price,
+            stock_value: product.stock_value(),
+            tags: &product.tags,
+        }
+    }
+}
+
+// One page of filtered and sorted products. `total` counts matching products across all
+// pages.
+#[derive(Debug, Serialize)]
+pub struct ProductListing<'a> {
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+    pub products: Vec<ProductRow<'a>>,
+}
+
+// A category in list-categories, with its depth in the tree and the number of products
+// filed directly under it.
+#[derive(Debug, Serialize)]
+pub struct CategoryRow<'a> {
+    pub name: &'a str,
+    pub parent: Option<&'a str>,
+    pub depth: usize,
+    pub products: usize,
+}
+
+// Stock held in one unit of measure. Totals keep units apart, since stock measured in
+// different units cannot be added together.
+#[derive(Debug, Serialize)]
+pub struct StockTotal {
+    pub quantity: Quantity,
+    pub unit: Unit,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InventoryTotals {
+    pub items: Vec<StockTotal>,
+    pub cost_value: Money,
+    pub retail_value: Money,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SalesTotals {
+    pub revenue: Money,
+    pub cost_of_goods: Money,
+    pub gross_margin: Money,
+    pub margin_percent: Decimal,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurchaseTotals {
+    pub purchase_cost: Money,
+}
+
+// The records of one category in a report grouped by category.
+#[derive(Debug, Serialize)]
+pub struct CategoryGroup<R, T> {
+    pub category: String,
+    pub records: Vec<R>,
+    pub totals: T,
+}
+
+// A report as data rather than text, for JSON output. `categories` is null unless the
+// report is grouped by category.
+#[derive(Debug, Serialize)]
+pub struct ReportData<'a, R, T> {
+    pub report: &'static str,
+    pub base_currency: &'a str,
+    pub records: Vec<R>,
+    pub totals: T,
+    pub categories: Option<Vec<CategoryGroup<R, T>>>,
+}
+
+// A record as one tab-separated line of plain output, for scripts.
+pub trait PlainRecord {
+    fn fields(&self) -> Vec<String>;
+}
+
+impl<T: PlainRecord> PlainRecord for &T {
+    fn fields(&self) -> Vec<String> {
+        (*self).fields()
+    }
+}
+
+impl PlainRecord for ProductRow<'_> {
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.name.to_string(),
+            self.sku.unwrap_or_default().to_string(),
+            self.category.unwrap_or_default().to_string(),
+            self.status.to_string(),
+            self.quantity.to_string(),
+            self.unit.to_string(),
+            self.cost_price.to_string(),
+            self.retail_price.to_string(),
+            self.stock_value.to_string(),
+            self.tags.join(","),
+        ]
+    }
+}
+
+impl PlainRecord for CategoryRow<'_> {
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.name.to_string(),
+            self.parent.unwrap_or_default().to_string(),
+            self.depth.to_string(),
+            self.products.to_string(),
+        ]
+    }
+}
+
+impl PlainRecord for NearDuplicate<'_> {
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.first.name.clone(),
+            self.second.name.clone(),
+            self.reason.to_string(),
+        ]
+    }
+}
+
+impl PlainRecord for Sale {
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.timestamp.to_rfc3339(),
+            self.product_name.clone(),
+            self.quantity.to_string(),
+            self.unit.to_string(),
+            self.sale_price.to_string(),
+            self.currency.clone(),
+            self.base_total.to_string(),
+            self.cost_of_goods.to_string(),
+            self.profit.to_string(),
+        ]
+    }
+}
+
+impl PlainRecord for Purchase {
+    fn fields(&self) -> Vec<String> {
+        vec![
+            self.timestamp.to_rfc3339(),
+            self.product_name.clone(),
+            self.quantity.to_string(),
+            self.unit.to_string(),
+            self.purchase_price.to_string(),
+            self.currency.clone(),
+            self.base_total_cost.to_string(),
+        ]
+    }
+}
 
 pub struct Reporter;
 
 impl Reporter {
-    pub fn generate_inventory_report(inventory: &[Product]) -> String {
+    pub fn generate_inventory_report(
+        inventory: &[Product],
+        base_currency: &str,
+        categories: Option<&CategoryLookup>,
+    ) -> String {
         let mut report = String::from("INVENTORY REPORT\n================\n");
 
-        if inventory.is_empty() {
+        let listed: Vec<&Product> = inventory.iter().filter(|p| !p.is_archived()).collect();
+
+        if listed.is_empty() {
             report.push_str("No products in inventory.\n");
             return report;
         }
 
-        for product in inventory {
-            report.push_str(&format!(
-                "Product: {} | Price: ${:.2} | Qty: {} | Description: {}\n",
-                product.name, product.price, product.quantity, product.description
-            ));
-        }
+        // Variants are listed under their parent rather than on their own, unless the
+        // parent is missing from the report.
+        let families: Vec<Family> = listed
+            .iter()
+            .filter(|p| {
+                p.variant
+                    .as_ref()
+                    .is_none_or(|v| !listed.iter().any(|parent| parent.name == v.parent))
+            })
+            .map(|product| Family {
+                product,
+                variants: listed
+                    .iter()
+                    .copied()
+                    .filter(|p| p.is_variant_of(&product.name))
+                    .collect(),
+            })
+            .collect();
+        let families: Vec<&Family> = families.iter().collect();
 
-        let total_value: f64 = inventory.iter().map(|p| p.price * p.quantity as f64).sum();
-        let total_items: u32 = inventory.iter().map(|p| p.quantity).sum();
-        report.push_str(&format!(
-            "\nTotal Items: {total_items} | Total Value: ${total_value:.2}\n"
-        ));
+        Self::push_lines(
+            &mut report,
+            &families,
+            categories,
+            |family| &family.product.name,
+            |family| {
+                let product = family.product;
+                let quantity: Quantity = family.members().map(|p| p.quantity).sum();
+                let mut lines = format!(
+                    "Product: {} | Cost: {} | Retail: {} | Qty: {} {} | Status: {} | Description: {}\n",
+                    product.name,
+                    Self::amount(product.cost_price, base_currency),
+                    Self::amount(product.retail_price, base_currency),
+                    quantity,
+                    product.unit,
+                    product.status,
+                    product.description
+                );
+                for variant in &family.variants {
+                    let Some(details) = &variant.variant else {
+                        continue;
+                    };
+                    let options: Vec<String> = details
+                        .options
+                        .iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect();
+                    lines.push_str(&format!(
+                        "  Variant: {} | SKU: {} | Options: {} | Cost: {} | Retail: {} | Qty: {} {} | Status: {}\n",
+                        variant.name,
+                        details.sku,
+                        options.join(", "),
+                        Self::amount(variant.cost_price, base_currency),
+                        Self::amount(variant.retail_price, base_currency),
+                        variant.quantity,
+                        variant.unit,
+                        variant.status
+                    ));
+                }
+                lines
+            },
+            |families, label| {
+                let products: Vec<&Product> = families.iter().flat_map(|f| f.members()).collect();
+                Self::inventory_totals(&products, label, base_currency)
+            },
+        );
+        report.push('\n');
+        report.push_str(&Self::inventory_totals(&listed, "Total", base_currency));
         report
     }
 
-    pub fn generate_sales_report(sales: &[Sale]) -> String {
+    pub fn generate_sales_report(
+        sales: &[Sale],
+        base_currency: &str,
+        categories: Option<&CategoryLookup>,
+    ) -> String {
         let mut report = String::from("SALES REPORT\n============\n");
 
         if sales.is_empty() {
@@ -36,27 +303,38 @@ impl Reporter {
             return report;
         }
 
-        for sale in sales {
-            report.push_str(&format!(
-                "Product: {} | Qty: {} | Price: ${:.2} | Total: ${:.2} | Profit: ${:.2} | Date: {}\n",
-                sale.product_name,
-                sale.quantity,
-                sale.sale_price,
-                sale.total,
-                sale.profit,
-                sale.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
-            ));
-        }
-
-        let total_sales: f64 = sales.iter().map(|s| s.total).sum();
-        let total_profit: f64 = sales.iter().map(|s| s.profit).sum();
-        report.push_str(&format!(
-            "\nTotal Sales: ${total_sales:.2} | Total Profit: ${total_profit:.2}\n"
-        ));
+        let sales: Vec<&Sale> = sales.iter().collect();
+        Self::push_lines(
+            &mut report,
+            &sales,
+            categories,
+            |sale| &sale.product_name,
+            |sale| {
+                format!(
+                    "Product: {} | Qty: {} {} | Price: {} | Revenue: {} | COGS: {} | Gross Margin: {} ({:.1}%) | Date: {}\n",
+                    sale.product_name,
+                    sale.quantity,
+                    sale.unit,
+                    Self::amount(sale.sale_price, &sale.currency),
+                    Self::converted(sale.total, &sale.currency, sale.base_total, base_currency),
+                    Self::amount(sale.cost_of_goods, base_currency),
+                    Self::amount(sale.profit, base_currency),
+                    sale.profit.percent_of(sale.base_total),
+                    sale.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+                )
+            },
+            |sales, label| Self::sales_totals(sales, label, base_currency),
+        );
+        report.push('\n');
+        report.push_str(&Self::sales_totals(&sales, "Total", base_currency));
         report
     }
 
-    pub fn generate_purchase_report(purchases: &[Purchase]) -> String {
+    pub fn generate_purchase_report(
+        purchases: &[Purchase],
+        base_currency: &str,
+        categories: Option<&CategoryLookup>,
+    ) -> String {
         let mut report = String::from("PURCHASE REPORT\n===============\n");
 
         if purchases.is_empty() {
@@ -64,19 +342,382 @@ impl Reporter {
             return report;
         }
 
-        for purchase in purchases {
-            report.push_str(&format!(
-                "Product: {} | Qty: {} | Unit Price: ${:.2} | Total: ${:.2} | Date: {}\n",
-                purchase.product_name,
-                purchase.quantity,
-                purchase.purchase_price,
-                purchase.total_cost,
-                purchase.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
-            ));
+        let purchases: Vec<&Purchase> = purchases.iter().collect();
+        Self::push_lines(
+            &mut report,
+            &purchases,
+            categories,
+            |purchase| &purchase.product_name,
+            |purchase| {
+                format!(
+                    "Product: {} | Qty: {} {}{} | Unit Price: {} | Total: {} | Date: {}\n",
+                    purchase.product_name,
+                    purchase.quantity,
+                    purchase.unit,
+                    purchase
+                        .pack
+                        .as_ref()
+                        .map_or(String::new(), |p| format!(" ({} {})", p.quantity, p.pack)),
+                    Self::converted(
+                        purchase.purchase_price,
+                        &purchase.currency,
+                        purchase.base_purchase_price,
+                        base_currency
+                    ),
+                    Self::converted(
+                        purchase.total_cost,
+                        &purchase.currency,
+                        purchase.base_total_cost,
+                        base_currency
+                    ),
+                    purchase.timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+                )
+            },
+            |purchases, label| Self::purchase_totals(purchases, label, base_currency),
+        );
+        report.push('\n');
+        report.push_str(&Self::purchase_totals(&purchases, "Total", base_currency));
+        report
+    }
+
+    // Labelled fields of one product, for show-product. Empty fields are left out.
+    pub fn product_details(product: &Product, base_currency: &str) -> Vec<(&'static str, String)> {
+        let mut details = vec![("Name", product.name.clone())];
+        if let Some(variant) = &product.variant {
+            details.push(("Variant of", variant.parent.clone()));
+            details.push(("SKU", variant.sku.clone()));
+        }
+        details.push(("Description", product.description.clone()));
+        details.push(("Status", product.status.to_string()));
+        if let Some(category) = &product.category {
+            details.push(("Category", category.clone()));
+        }
+        details.push(("Quantity", format!("{} {}", product.quantity, product.unit)));
+        details.push((
+            "Cost Price",
+            Self::amount(product.cost_price, base_currency),
+        ));
+        details.push((
+            "Retail Price",
+            Self::amount(product.retail_price, base_currency),
+        ));
+        details.push((
+            "Stock Value",
+            Self::amount(product.stock_value(), base_currency),
+        ));
+        let lists = [
+            ("Aliases", product.aliases.clone()),
+            ("Tags", product.tags.clone()),
+            (
+                "Packs",
+                product
+                    .packs
+                    .iter()
+                    .map(|pack| match &pack.of {
+                        Some(of) => format!("{} ({} {of})", pack.name, pack.size),
+                        None => format!("{} ({} {})", pack.name, pack.size, product.unit),
+                    })
+                    .collect(),
+            ),
+            (
+                "Attributes",
+                product
+                    .attributes
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect(),
+            ),
+        ];
+        for (label, values) in lists {
+            if !values.is_empty() {
+                details.push((label, values.join(", ")));
+            }
+        }
+        details
+    }
+
+    pub fn generate_product_list(listing: &ProductListing, base_currency: &str) -> String {
+        if listing.products.is_empty() {
+            return if listing.total == 0 {
+                "No products match.\n".to_string()
+            } else {
+                format!("Page {} is past the end of the listing.\n", listing.page)
+            };
         }
 
-        let total_cost: f64 = purchases.iter().map(|p| p.total_cost).sum();
-        report.push_str(&format!("\nTotal Purchase Cost: ${total_cost:.2}\n"));
-        report
+        let header = [
+            "Name", "SKU", "Category", "Status", "Qty", "Unit", "Cost", "Retail", "Tags",
+        ];
+        let rows: Vec<[String; 9]> = listing
+            .products
+            .iter()
+            .map(|row| {
+                [
+                    row.name.to_string(),
+                    row.sku.unwrap_or("-").to_string(),
+                    row.category.unwrap_or("-").to_string(),
+                    row.status.to_string(),
+                    row.quantity.to_string(),
+                    row.unit.to_string(),
+                    Self::amount(row.cost_price, base_currency),
+                    Self::amount(row.retail_price, base_currency),
+                    row.tags.join(", "),
+                ]
+            })
+            .collect();
+        let mut widths = header.map(|title| title.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let format_row = |cells: &[String]| {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            format!("{}\n", line.join(" | ").trim_end())
+        };
+        let mut list = format_row(&header.map(str::to_string));
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        list.push_str(&format!("{}\n", rule.join("-+-")));
+        rows.iter().for_each(|row| list.push_str(&format_row(row)));
+
+        let pages = listing.total.div_ceil(listing.per_page);
+        list.push_str(&format!(
+            "\nPage {} of {pages} ({} products)\n",
+            listing.page, listing.total
+        ));
+        list
+    }
+
+    fn inventory_totals_of(products: &[&Product]) -> InventoryTotals {
+        InventoryTotals {
+            items: Self::quantities(products.iter().map(|p| (p.quantity, p.unit))),
+            cost_value: products.iter().map(|p| p.stock_value()).sum(),
+            retail_value: products
+                .iter()
+                .map(|p| p.retail_price.times(p.quantity))
+                .sum(),
+        }
+    }
+
+    fn sales_totals_of(sales: &[&Sale]) -> SalesTotals {
+        let revenue: Money = sales.iter().map(|s| s.base_total).sum();
+        let gross_margin: Money = sales.iter().map(|s| s.profit).sum();
+        SalesTotals {
+            revenue,
+            cost_of_goods: sales.iter().map(|s| s.cost_of_goods).sum(),
+            gross_margin,
+            margin_percent: gross_margin.percent_of(revenue),
+        }
+    }
+
+    fn purchase_totals_of(purchases: &[&Purchase]) -> PurchaseTotals {
+        PurchaseTotals {
+            purchase_cost: purchases.iter().map(|p| p.base_total_cost).sum(),
+        }
+    }
+
+    fn inventory_totals(products: &[&Product], label: &str, base_currency: &str) -> String {
+        let totals = Self::inventory_totals_of(products);
+        let items: Vec<String> = totals
+            .items
+            .iter()
+            .map(|total| format!("{} {}", total.quantity, total.unit))
+            .collect();
+        format!(
+            "{label} Items: {} | {label} Cost Value: {} | {label} Retail Value: {}\n",
+            items.join(", "),
+            Self::amount(totals.cost_value, base_currency),
+            Self::amount(totals.retail_value, base_currency)
+        )
+    }
+
+    fn sales_totals(sales: &[&Sale], label: &str, base_currency: &str) -> String {
+        let totals = Self::sales_totals_of(sales);
+        format!(
+            "{label} Revenue: {} | {label} COGS: {} | Gross Margin: {} ({:.1}%)\n",
+            Self::amount(totals.revenue, base_currency),
+            Self::amount(totals.cost_of_goods, base_currency),
+            Self::amount(totals.gross_margin, base_currency),
+            totals.margin_percent
+        )
+    }
+
+    fn purchase_totals(purchases: &[&Purchase], label: &str, base_currency: &str) -> String {
+        let totals = Self::purchase_totals_of(purchases);
+        format!(
+            "{label} Purchase Cost: {}\n",
+            Self::amount(totals.purchase_cost, base_currency)
+        )
+    }
+
+    // Appends a line per record. With categories, records are grouped under a heading per
+    // category path, in path order with uncategorized records last, and each group ends
+    // with a subtotal line.
+    fn push_lines<T>(
+        report: &mut String,
+        records: &[&T],
+        categories: Option<&CategoryLookup>,
+        product_name: impl Fn(&T) -> &str,
+        line: impl Fn(&T) -> String,
+        totals: impl Fn(&[&T], &str) -> String,
+    ) {
+        let Some(categories) = categories else {
+            records
+                .iter()
+                .for_each(|record| report.push_str(&line(record)));
+            return;
+        };
+
+        for (category, group) in Self::by_category(records, categories, product_name) {
+            report.push_str(&format!("\n[{category}]\n"));
+            group
+                .iter()
+                .for_each(|record| report.push_str(&line(record)));
+            report.push_str(&totals(&group, "Subtotal"));
+        }
+    }
+
+    // Records grouped by category path, in path order with uncategorized records last.
+    fn by_category<'r, T>(
+        records: &[&'r T],
+        categories: &CategoryLookup,
+        product_name: impl Fn(&T) -> &str,
+    ) -> Vec<(String, Vec<&'r T>)> {
+        let mut groups: BTreeMap<(bool, String), Vec<&T>> = BTreeMap::new();
+        for record in records {
+            let category = categories.category_of(product_name(record));
+            let key = (
+                category.is_none(),
+                category.unwrap_or_else(|| UNCATEGORIZED.to_string()),
+            );
+            groups.entry(key).or_default().push(record);
+        }
+        groups
+            .into_iter()
+            .map(|((_, category), group)| (category, group))
+            .collect()
+    }
+
+    // Builds the data form of a report from its records, with the same grouping and
+    // totals as the text form.
+    fn report_data<'a, T, R, Totals>(
+        report: &'static str,
+        base_currency: &'a str,
+        records: &[&'a T],
+        categories: Option<&CategoryLookup>,
+        product_name: impl Fn(&T) -> &str,
+        row: impl Fn(&'a T) -> R,
+        totals: impl Fn(&[&T]) -> Totals,
+    ) -> ReportData<'a, R, Totals> {
+        ReportData {
+            report,
+            base_currency,
+            records: records.iter().map(|record| row(record)).collect(),
+            totals: totals(records),
+            categories: categories.map(|categories| {
+                Self::by_category(records, categories, product_name)
+                    .into_iter()
+                    .map(|(category, group)| CategoryGroup {
+                        category,
+                        records: group.iter().map(|record| row(record)).collect(),
+                        totals: totals(&group),
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn inventory_data<'a>(
+        inventory: &'a [Product],
+        base_currency: &'a str,
+        categories: Option<&CategoryLookup>,
+    ) -> ReportData<'a, ProductRow<'a>, InventoryTotals> {
+        let listed: Vec<&Product> = inventory.iter().filter(|p| !p.is_archived()).collect();
+        Self::report_data(
+            "inventory",
+            base_currency,
+            &listed,
+            categories,
+            |product| &product.name,
+            ProductRow::from,
+            Self::inventory_totals_of,
+        )
+    }
+
+    pub fn sales_data<'a>(
+        sales: &'a [Sale],
+        base_currency: &'a str,
+        categories: Option<&CategoryLookup>,
+    ) -> ReportData<'a, &'a Sale, SalesTotals> {
+        let sales: Vec<&Sale> = sales.iter().collect();
+        Self::report_data(
+            "sales",
+            base_currency,
+            &sales,
+            categories,
+            |sale| &sale.product_name,
+            |sale| sale,
+            Self::sales_totals_of,
+        )
+    }
+
+    pub fn purchase_data<'a>(
+        purchases: &'a [Purchase],
+        base_currency: &'a str,
+        categories: Option<&CategoryLookup>,
+    ) -> ReportData<'a, &'a Purchase, PurchaseTotals> {
+        let purchases: Vec<&Purchase> = purchases.iter().collect();
+        Self::report_data(
+            "purchases",
+            base_currency,
+            &purchases,
+            categories,
+            |purchase| &purchase.product_name,
+            |purchase| purchase,
+            Self::purchase_totals_of,
+        )
+    }
+
+    // One tab-separated line per record, without headings or totals.
+    pub fn generate_plain<R: PlainRecord>(records: &[R]) -> String {
+        records
+            .iter()
+            .map(|record| format!("{}\n", record.fields().join("\t")))
+            .collect()
+    }
+
+    // Totals quantities separately for each unit, e.g. 50 each and 12.5 kg.
+    fn quantities(quantities: impl Iterator<Item = (Quantity, Unit)>) -> Vec<StockTotal> {
+        let mut totals: Vec<StockTotal> = Vec::new();
+        for (quantity, unit) in quantities {
+            match totals.iter_mut().find(|total| total.unit == unit) {
+                Some(total) => total.quantity += quantity,
+                None => totals.push(StockTotal { quantity, unit }),
+            }
+        }
+        totals
+    }
+
+    fn amount(value: Money, currency: &str) -> String {
+        format!("{value} {currency}")
+    }
+
+    // Shows an amount in its original currency, followed by its base-currency value when
+    // the two differ.
+    fn converted(value: Money, currency: &str, base_value: Money, base_currency: &str) -> String {
+        if currency == base_currency {
+            Self::amount(value, currency)
+        } else {
+            format!(
+                "{} ({})",
+                Self::amount(value, currency),
+                Self::amount(base_value, base_currency)
+            )
+        }
     }
 }
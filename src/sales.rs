@@ -1,5 +1,13 @@
-use crate::inventory::Product;
+use crate::costing::{CostLayer, CostingMethod, consume_layers, layers_quantity};
+use crate::currency::Conversion;
+use crate::error::StoreError;
+use crate::inventory::{Inventory, Product};
+use crate::money::{Money, validate_positive};
+use crate::purchase::Purchase;
+use crate::search::product_not_found;
+use crate::units::{Quantity, Unit, validate_positive_quantity};
 use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -7,84 +15,268 @@ use validator::Validate;
 pub struct Sale {
     #[validate(length(min = 1, message = "Product name must be at least 1 character"))]
     pub product_name: String,
-    #[validate(range(min = 1, message = "Quantity must be at least 1"))]
-    pub quantity: u32,
-    #[validate(range(min = 0.01, message = "Sale price must be greater than 0"))]
-    pub sale_price: f64,
-    pub profit: f64,
-    pub total: f64,
+    #[validate(custom(
+        function = "validate_positive_quantity",
+        message = "Quantity must be greater than 0"
+    ))]
+    pub quantity: Quantity,
+    #[serde(default)]
+    pub unit: Unit,
+    #[validate(custom(
+        function = "validate_positive",
+        message = "Sale price must be greater than 0"
+    ))]
+    pub sale_price: Money,
+    pub profit: Money,
+    pub total: Money,
+    #[serde(default)]
+    pub unit_cost: Money,
+    #[serde(default)]
+    pub cost_of_goods: Money,
+    pub currency: String,
+    pub exchange_rate: Decimal,
+    pub base_total: Money,
     pub timestamp: DateTime<Utc>,
 }
 
 pub trait Sales {
+    #[allow(clippy::too_many_arguments)]
     fn record_sale(
         &mut self,
         product_name: &str,
-        quantity: u32,
-        sale_price: f64,
-        inventory: &mut Vec<Product>,
-    ) -> Result<Sale, String>;
+        quantity: Quantity,
+        unit: Option<Unit>,
+        sale_price: Option<Money>,
+        conversion: &Conversion,
+        costing_method: CostingMethod,
+        inventory: &mut impl Inventory,
+    ) -> Result<Sale, StoreError>;
+    fn relink_product(&mut self, product: &Product);
+    fn recompute_profits(
+        &mut self,
+        purchases: &[Purchase],
+        costing_method: CostingMethod,
+        inventory: &mut impl Inventory,
+    ) -> usize;
 }
 
 impl Sales for Vec<Sale> {
+    // `quantity` and `sale_price` are in `unit`, which defaults to the product's own unit.
+    // The sale is recorded in the product's unit.
+    #[allow(clippy::too_many_arguments)]
     fn record_sale(
         &mut self,
         product_name: &str,
-        quantity: u32,
-        sale_price: f64,
-        inventory: &mut Vec<Product>,
-    ) -> Result<Sale, String> {
-        let inventory_product = inventory
-            .iter_mut()
-            .find(|p| p.name == product_name)
-            .ok_or_else(|| format!("Product {product_name} not found"))?;
+        quantity: Quantity,
+        unit: Option<Unit>,
+        sale_price: Option<Money>,
+        conversion: &Conversion,
+        costing_method: CostingMethod,
+        inventory: &mut impl Inventory,
+    ) -> Result<Sale, StoreError> {
+        let index = inventory
+            .position(product_name)
+            .ok_or_else(|| product_not_found(product_name, inventory))?;
+        let mut inventory_product = inventory.products()[index].clone();
+        let skus: Vec<&str> = inventory
+            .variants_of(&inventory_product.name)
+            .into_iter()
+            .filter_map(|p| p.variant.as_ref().map(|v| v.sku.as_str()))
+            .collect();
+        if !skus.is_empty() {
+            return Err(StoreError::invalid(format!(
+                "Product {} has variants; sell one of them: {}",
+                inventory_product.name,
+                skus.join(", ")
+            )));
+        }
 
-        if inventory_product.quantity < quantity {
-            return Err(format!(
-                "Insufficient stock for '{}'. Available: {}, Requested: {}",
-                inventory_product.name, inventory_product.quantity, quantity
-            ));
+        if inventory_product.is_archived() {
+            return Err(StoreError::invalid(format!(
+                "Product {} is archived and cannot be sold",
+                inventory_product.name
+            )));
         }
 
-        inventory_product.quantity -= quantity;
+        let product_unit = inventory_product.unit;
+        let unit = unit.unwrap_or(product_unit);
+        let ratio = unit.ratio_to(product_unit)?;
+        let quantity = unit.convert(quantity, product_unit)?;
 
+        inventory_product.reconcile_cost_layers(costing_method);
+        if inventory_product.quantity < quantity {
+            return Err(StoreError::InsufficientStock {
+                product: inventory_product.name,
+                requested: quantity,
+                available: inventory_product.quantity,
+                unit: product_unit,
+            });
+        }
+
+        let sale_price = match sale_price {
+            Some(price) => price.convert(ratio),
+            None => conversion
+                .to_local(inventory_product.retail_price)
+                .round_cents(),
+        };
+        let mut cost_layers = inventory_product.cost_layers.clone();
+        let cost = consume_layers(&mut cost_layers, quantity, costing_method);
+        let total = sale_price.times(quantity);
+        let base_total = conversion.to_base(total).round_cents();
         let sale = Sale {
             product_name: inventory_product.name.clone(),
             quantity,
+            unit: product_unit,
             sale_price,
-            profit: {
-                let cost = inventory_product.price * quantity as f64;
-                let sale_total = sale_price * quantity as f64;
-                sale_total - cost
-            },
-            total: sale_price * quantity as f64,
+            profit: base_total - cost,
+            total,
+            unit_cost: cost.per_unit(quantity),
+            cost_of_goods: cost,
+            currency: conversion.currency.clone(),
+            exchange_rate: conversion.rate,
+            base_total,
             timestamp: Utc::now(),
         };
 
-        sale.validate()
-            .map_err(|errors| format!("Validation errors: {errors:#?}"))?;
+        sale.validate()?;
+        inventory_product.quantity -= quantity;
+        inventory_product.cost_layers = cost_layers;
+        inventory.update_product(index, |product| *product = inventory_product);
         self.push(sale.clone());
         Ok(sale)
     }
-}
 
+    fn relink_product(&mut self, product: &Product) {
+        self.iter_mut()
+            .filter(|s| product.matches(&s.product_name))
+            .for_each(|s| s.product_name = product.name.clone());
+    }
+
+    fn recompute_profits(
+        &mut self,
+        purchases: &[Purchase],
+        costing_method: CostingMethod,
+        inventory: &mut impl Inventory,
+    ) -> usize {
+        let mut recomputed = 0;
+        for index in 0..inventory.products().len() {
+            let product = &inventory.products()[index];
+            let mut product_purchases: Vec<&Purchase> = purchases
+                .iter()
+                .filter(|p| product.matches(&p.product_name))
+                .collect();
+            let mut product_sales: Vec<&mut Sale> = self
+                .iter_mut()
+                .filter(|s| product.matches(&s.product_name))
+                .collect();
+
+            // Stock that was on hand before the first recorded purchase, e.g. from add-product.
+            let purchased: Quantity = product_purchases.iter().map(|p| p.quantity).sum();
+            let sold: Quantity = product_sales.iter().map(|s| s.quantity).sum();
+            let opening = (product.quantity + sold).max(purchased) - purchased;
+            let mut layers = Vec::new();
+            if opening > Quantity::ZERO {
+                layers.push(CostLayer {
+                    quantity: opening,
+                    unit_cost: product.cost_price,
+                });
+            }
+
+            product_purchases.sort_by_key(|p| p.timestamp);
+            product_sales.sort_by_key(|s| s.timestamp);
+            let mut pending_purchases = product_purchases.into_iter().peekable();
+            for sale in product_sales {
+                while let Some(purchase) =
+                    pending_purchases.next_if(|p| p.timestamp <= sale.timestamp)
+                {
+                    layers.push(CostLayer {
+                        quantity: purchase.quantity,
+                        unit_cost: purchase.base_purchase_price,
+                    });
+                }
+                let layered = layers_quantity(&layers);
+                if layered < sale.quantity {
+                    layers.push(CostLayer {
+                        quantity: sale.quantity - layered,
+                        unit_cost: product.cost_price,
+                    });
+                }
+
+                let cost = consume_layers(&mut layers, sale.quantity, costing_method);
+                sale.cost_of_goods = cost;
+                sale.unit_cost = cost.per_unit(sale.quantity);
+                sale.profit = sale.base_total - cost;
+                recomputed += 1;
+            }
+            for purchase in pending_purchases {
+                layers.push(CostLayer {
+                    quantity: purchase.quantity,
+                    unit_cost: purchase.base_purchase_price,
+                });
+            }
+
+            inventory.update_product(index, |product| {
+                product.cost_layers = layers;
+                product.reconcile_cost_layers(costing_method);
+            });
+        }
+        recomputed
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::inventory::Inventory;
+    use crate::inventory::{Inventory, ProductStatus};
     use super::*;
+    use std::collections::BTreeMap;
 
     #[test]
     fn test_record_sale() {
         let mut sales = Vec::new();
         let mut inventory = vec![Product {
             name: "Test Product".to_string(),
-            price: 10.0,
-            quantity: 10,
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
+            description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
+        }];
+        assert!(sales.record_sale("Test Product", 5.into(), None, Some(Money::from_cents(500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_ok());
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 5.into());
+    }
+
+    #[test]
+    fn test_record_sale_defaults_to_retail_price() -> Result<(), StoreError> {
+        let mut sales = Vec::new();
+        let mut inventory = vec![Product {
+            name: "Test Product".to_string(),
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
             description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
         }];
-        assert!(sales.record_sale("Test Product", 5, 5.0, &mut inventory).is_ok());
-        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 5);
+        let sale = sales.record_sale("Test Product", 2.into(), None, None, &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(sale.sale_price, Money::from_cents(1500));
+        assert_eq!(sale.total, Money::from_cents(3000));
+        assert_eq!(sale.profit, Money::from_cents(1000));
+        Ok(())
     }
 
     #[test]
@@ -92,12 +284,23 @@ mod tests {
         let mut sales = Vec::new();
         let mut inventory = vec![Product {
             name: "Test Product".to_string(),
-            price: 10.0,
-            quantity: 5,
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 5.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
             description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
         }];
-        assert!(sales.record_sale("Test Product", 6, 5.0, &mut inventory).is_err());
-        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 5);
+        let error = sales.record_sale("Test Product", 6.into(), None, Some(Money::from_cents(500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).unwrap_err();
+        assert_eq!(error, StoreError::InsufficientStock { product: "Test Product".to_string(), requested: 6.into(), available: 5.into(), unit: Unit::Each });
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 5.into());
     }
 
     #[test]
@@ -105,11 +308,205 @@ mod tests {
         let mut sales = Vec::new();
         let mut inventory = vec![Product {
             name: "Test Product".to_string(),
-            price: 10.0,
-            quantity: 10,
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
             description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
         }];
-        assert!(sales.record_sale("", 5, 5.0, &mut inventory).is_err());
-        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 10);
+        assert!(sales.record_sale("", 5.into(), None, Some(Money::from_cents(500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 10.into());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_relink_product_after_rename() -> Result<(), StoreError> {
+        let mut sales = Vec::new();
+        let mut inventory = vec![Product {
+            name: "Test Product".to_string(),
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
+            description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
+        }];
+        sales.record_sale("Test Product", 5.into(), None, Some(Money::from_cents(500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        inventory.rename_item("Test Product", "Renamed Product")?;
+        sales.relink_product(inventory.get_item("Renamed Product").unwrap());
+        assert_eq!(sales[0].product_name, "Renamed Product");
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_sale_archived_product() -> Result<(), StoreError> {
+        let mut sales = Vec::new();
+        let mut inventory = vec![Product {
+            name: "Test Product".to_string(),
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
+            description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Archived,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
+        }];
+        assert!(sales.record_sale("Test Product", 5.into(), None, Some(Money::from_cents(500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        assert_eq!(inventory.get_item("Test Product").unwrap().quantity, 10.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_sale_consumes_cost_layers() -> Result<(), StoreError> {
+        let mut sales = Vec::new();
+        let mut inventory = vec![Product {
+            name: "Test Product".to_string(),
+            cost_price: Money::from_cents(1200),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
+            description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: vec![
+                CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(800) },
+                CostLayer { quantity: 5.into(), unit_cost: Money::from_cents(1200) },
+            ],
+        }];
+        let sale = sales.record_sale("Test Product", 6.into(), None, Some(Money::from_cents(1500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(sale.profit, Money::from_cents(9000) - Money::from_cents(5200));
+        let sale = sales.record_sale("Test Product", 2.into(), None, Some(Money::from_cents(1500)), &Conversion::base("USD"), CostingMethod::Lifo, &mut inventory)?;
+        assert_eq!(sale.profit, Money::from_cents(3000) - Money::from_cents(2400));
+        assert_eq!(inventory[0].cost_layers, vec![CostLayer { quantity: 2.into(), unit_cost: Money::from_cents(1200) }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recompute_profits() -> Result<(), StoreError> {
+        use crate::purchase::Purchases;
+
+        let mut sales = Vec::new();
+        let mut purchases = Vec::new();
+        let mut inventory = Vec::new();
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(800), Some(Money::from_cents(1500)), "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        purchases.record_purchase("Test Product", 5.into(), None, None, Money::from_cents(1200), None, "Test description", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        sales.record_sale("Test Product", 6.into(), None, Some(Money::from_cents(1500)), &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(sales[0].cost_of_goods, Money::from_cents(5200));
+
+        assert_eq!(sales.recompute_profits(&purchases, CostingMethod::Lifo, &mut inventory), 1);
+        assert_eq!(sales[0].cost_of_goods, Money::from_cents(6800));
+        assert_eq!(sales[0].profit, Money::from_cents(9000) - Money::from_cents(6800));
+        assert_eq!(inventory[0].cost_layers, vec![CostLayer { quantity: 4.into(), unit_cost: Money::from_cents(800) }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_sale_in_foreign_currency() -> Result<(), StoreError> {
+        let mut sales = Vec::new();
+        let mut inventory = vec![Product {
+            name: "Test Product".to_string(),
+            cost_price: Money::from_cents(1000),
+            retail_price: Money::from_cents(1500),
+            quantity: 10.into(),
+            unit: Unit::Each,
+            packs: Vec::new(),
+            description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
+        }];
+        let conversion = Conversion { currency: "EUR".to_string(), rate: Decimal::new(125, 2) };
+        let sale = sales.record_sale("Test Product", 2.into(), None, None, &conversion, CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(sale.sale_price, Money::from_cents(1200));
+        assert_eq!(sale.total, Money::from_cents(2400));
+        assert_eq!(sale.base_total, Money::from_cents(3000));
+        assert_eq!(sale.profit, Money::from_cents(1000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_sale_in_another_unit() -> Result<(), Box<dyn std::error::Error>> {
+        let mut sales = Vec::new();
+        let mut inventory = vec![Product {
+            name: "Test Product".to_string(),
+            cost_price: Money::from_cents(400),
+            retail_price: Money::from_cents(1000),
+            quantity: "2.5".parse()?,
+            unit: Unit::Kg,
+            packs: Vec::new(),
+            description: "Test description".to_string(),
+            aliases: Vec::new(),
+            status: ProductStatus::Active,
+            category: None,
+            attributes: BTreeMap::new(),
+            tags: Vec::new(),
+            variant: None,
+            cost_layers: Vec::new(),
+        }];
+        let sale = sales.record_sale("Test Product", 750.into(), Some(Unit::G), None, &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(sale.quantity, "0.75".parse()?);
+        assert_eq!(sale.total, Money::from_cents(750));
+        assert_eq!(sale.cost_of_goods, Money::from_cents(300));
+        assert_eq!(inventory[0].quantity, "1.75".parse()?);
+        assert!(sales.record_sale("Test Product", 1.into(), Some(Unit::Litre), None, &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_sale_of_product_with_variants() -> Result<(), StoreError> {
+        let mut sales = Vec::new();
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("T-Shirt", Money::from_cents(600), Money::from_cents(1500), 0.into(), Unit::Each, "Cotton T-shirt")?;
+        inventory.add_variant("T-Shirt", "TS-M", [("size".to_string(), "M".to_string())].into(), 10.into(), None, None)?;
+        assert!(sales.record_sale("T-Shirt", 1.into(), None, None, &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).is_err());
+        let sale = sales.record_sale("TS-M", 1.into(), None, None, &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(sale.product_name, "T-Shirt (M)");
+        assert_eq!(sale.sale_price, Money::from_cents(1500));
+        Ok(())
+    }
+
+    #[test]
+    fn test_record_purchase_of_product_with_variants() -> Result<(), StoreError> {
+        use crate::purchase::Purchases;
+
+        let mut purchases = Vec::new();
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("T-Shirt", Money::from_cents(600), Money::from_cents(1500), 0.into(), Unit::Each, "Cotton T-shirt")?;
+        inventory.add_variant("T-Shirt", "TS-M", [("size".to_string(), "M".to_string())].into(), 0.into(), None, None)?;
+        let error = purchases.record_purchase("T-Shirt", 5.into(), None, None, Money::from_cents(600), None, "Cotton T-shirt", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory).unwrap_err();
+        assert_eq!(error.to_string(), "Product T-Shirt has variants; receive one of them: TS-M");
+        assert_eq!(inventory.get_item("T-Shirt").unwrap().quantity, 0.into());
+        purchases.record_purchase("TS-M", 5.into(), None, None, Money::from_cents(600), None, "Cotton T-shirt", &Conversion::base("USD"), CostingMethod::Fifo, &mut inventory)?;
+        assert_eq!(inventory.get_item("TS-M").unwrap().quantity, 5.into());
+        Ok(())
+    }
+}
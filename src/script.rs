@@ -0,0 +1,197 @@
+use crate::auth::Role;
+use crate::error::StoreError;
+use crate::output::Output;
+use crate::shell::parse_line;
+use crate::store::Store;
+use crate::{Commands, execute, preview};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// The outcome of a script whose changes were kept. Scripts that fail are rolled back and
+// reported as a StoreError::Script instead.
+#[derive(Debug, Serialize)]
+struct ScriptSummary {
+    committed: bool,
+    commands: usize,
+    skipped_lines: Vec<usize>,
+    lines: Vec<ScriptLine>,
+}
+
+// A line that ran, with what it printed, or a skipped line with its error.
+#[derive(Debug, Serialize)]
+struct ScriptLine {
+    line: usize,
+    command: String,
+    #[serde(skip_serializing_if = "Value::is_null")]
+    result: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<StoreError>,
+    #[serde(skip)]
+    text: String,
+}
+
+// Runs a script file of commands, one per line as in the shell, under the current login.
+pub fn run(
+    path: &Path,
+    skip_errors: bool,
+    store: &mut Store,
+    role: Role,
+    output: Output,
+) -> Result<(), StoreError> {
+    let script = fs::read_to_string(path)
+        .map_err(|err| StoreError::Storage(format!("{}: {err}", path.display())))?;
+    run_script(&script, skip_errors, store, role, output)
+}
+
+// The script's commands run against a copy of the store, which replaces the store only
+// once the whole script has run. A failing line stops the script and leaves the store
+// unchanged, unless `skip_errors` is set; then the failing line is undone and the script
+// carries on. Blank lines and lines starting with # are ignored. What the lines print is
+// held back until the end, so that nothing is reported for a script that is rolled back.
+fn run_script(
+    script: &str,
+    skip_errors: bool,
+    store: &mut Store,
+    role: Role,
+    output: Output,
+) -> Result<(), StoreError> {
+    let mut copy = store.clone();
+    let mut summary = ScriptSummary {
+        committed: true,
+        commands: 0,
+        skipped_lines: Vec::new(),
+        lines: Vec::new(),
+    };
+    for (index, line) in script.lines().enumerate() {
+        let (number, line) = (index + 1, line.trim());
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let before = skip_errors.then(|| copy.clone());
+        let (result, mut captured) = Output::capture(|| run_line(line, &mut copy, role, output));
+        let error = match (result, before) {
+            (Ok(()), _) => {
+                summary.commands += 1;
+                None
+            }
+            (Err(err), Some(before)) => {
+                summary.skipped_lines.push(number);
+                copy = before;
+                Some(err)
+            }
+            (Err(err), None) => {
+                return Err(StoreError::Script {
+                    line: number,
+                    command: line.to_string(),
+                    error: Box::new(err),
+                });
+            }
+        };
+        summary.lines.push(ScriptLine {
+            line: number,
+            command: line.to_string(),
+            text: std::mem::take(&mut captured.text),
+            result: captured.into_json(),
+            error,
+        });
+    }
+    *store = copy;
+
+    let message = format!(
+        "Script committed: {} commands run, {} lines skipped",
+        summary.commands,
+        summary.skipped_lines.len()
+    );
+    if output.is_json() {
+        return output.json(&summary);
+    }
+    for line in &summary.lines {
+        output.print(&line.text);
+        if !line.result.is_null() {
+            output.json(&line.result)?;
+        }
+        if let Some(err) = &line.error {
+            output.error(err);
+            output.note(&format!("Skipped line {}: {}", line.line, line.command));
+        }
+    }
+    output.done(&message, &summary)
+}
+
+fn run_line(line: &str, store: &mut Store, role: Role, output: Output) -> Result<(), StoreError> {
+    let cli = parse_line(line, output).map_err(|err| {
+        let message = err.to_string();
+        let first_line = message.lines().next().unwrap_or_default();
+        StoreError::invalid(first_line.trim_start_matches("error: "))
+    })?;
+    if matches!(
+        cli.command,
+        Commands::Run { .. } | Commands::Shell | Commands::Tui
+    ) {
+        return Err(StoreError::invalid(
+            "Scripts cannot run other scripts, the shell or the terminal interface",
+        ));
+    }
+    let output = Output::new(cli.output);
+    // A --dry-run line shows what it would change and leaves the script's store as it was.
+    if cli.dry_run && cli.command.changes_data() {
+        return preview(cli.command, store, role, output);
+    }
+    execute(cli.command, store, role, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::Inventory;
+    use crate::output::OutputFormat;
+
+    const SCRIPT: &str = "
+        # Month-end corrections
+        add-product -n Orange -c 0.50 -p 0.75 -q 10 -d 'Fresh fruit'
+        record-sale -n Orange -q 4
+        record-sale -n Orange -q 20
+        update-product -n Orange -q +5
+    ";
+
+    #[test]
+    fn test_script_commits_all_or_nothing() {
+        let output = Output::new(OutputFormat::Plain);
+        let mut store = Store::default();
+        let (result, captured) = Output::capture(|| run_script(SCRIPT, false, &mut store, Role::Manager, output));
+        let error = result.unwrap_err();
+        assert!(matches!(&error, StoreError::Script { line: 5, error, .. } if matches!(**error, StoreError::InsufficientStock { .. })));
+        assert_eq!(error.exit_code(), 6);
+        assert!(captured.text.is_empty(), "rolled-back lines print nothing");
+        assert!(store.inventory.products().is_empty() && store.sales.is_empty());
+
+        run_script(SCRIPT, true, &mut store, Role::Manager, output).unwrap();
+        assert_eq!(store.inventory.get_item("Orange").unwrap().quantity, 11.into());
+        assert_eq!(store.sales.len(), 1);
+    }
+
+    #[test]
+    fn test_script_json_summary() {
+        let mut store = Store::default();
+        let (result, captured) = Output::capture(|| run_script(SCRIPT, true, &mut store, Role::Manager, Output::new(OutputFormat::Json)));
+        result.unwrap();
+        assert_eq!(captured.values.len(), 1);
+        let summary = captured.into_json();
+        assert_eq!((summary["committed"].as_bool(), summary["commands"].as_u64()), (Some(true), Some(3)));
+        assert_eq!(summary["lines"][1]["result"]["product_name"], "Orange");
+        assert_eq!(summary["lines"][2]["error"]["kind"], "insufficient_stock");
+    }
+
+    #[test]
+    fn test_script_rejects_bad_lines() {
+        let output = Output::new(OutputFormat::Plain);
+        let mut store = Store::default();
+        for script in ["bogus-command", "run other.txt", "add-product -n 'Orange"] {
+            assert!(run_script(script, false, &mut store, Role::Manager, output).is_err());
+        }
+        let error = run_script("purge-product -n Orange", false, &mut store, Role::Clerk, output).unwrap_err();
+        assert_eq!(error.exit_code(), 3);
+    }
+}
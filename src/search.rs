@@ -0,0 +1,284 @@
+use crate::error::{Entity, StoreError};
+use crate::inventory::{Inventory, Product, name_key};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+
+// How much a matching word counts towards a product's score, by where it was found.
+const NAME_WEIGHT: f64 = 3.0;
+const SKU_WEIGHT: f64 = 3.0;
+const TAG_WEIGHT: f64 = 2.0;
+const DESCRIPTION_WEIGHT: f64 = 1.0;
+
+const SUGGESTIONS: usize = 3;
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+// Similarity of a query word to an indexed word, from 0 (no match) to 1 (same word). A
+// query word also matches words it starts, and words within a small edit distance, one
+// edit for words of 4-7 characters and two for longer words.
+fn similarity(query: &str, word: &str) -> f64 {
+    if query == word {
+        return 1.0;
+    }
+    if query.chars().count() >= 2 && word.starts_with(query) {
+        return 0.8;
+    }
+    let allowed = match query.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    };
+    match strsim::damerau_levenshtein(query, word) {
+        0 => 1.0,
+        distance if distance <= allowed => 0.7 - 0.1 * (distance - 1) as f64,
+        _ => 0.0,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    name: String,
+    words: Vec<(String, f64)>,
+}
+
+impl Entry {
+    // Archived products have no entry, so they are neither found nor suggested.
+    fn of(product: &Product) -> Option<Entry> {
+        if product.is_archived() {
+            return None;
+        }
+        let mut fields = vec![(product.name.as_str(), NAME_WEIGHT)];
+        fields.extend(product.aliases.iter().map(|a| (a.as_str(), NAME_WEIGHT)));
+        fields.extend(
+            product
+                .variant
+                .as_ref()
+                .map(|v| (v.sku.as_str(), SKU_WEIGHT)),
+        );
+        fields.extend(product.tags.iter().map(|t| (t.as_str(), TAG_WEIGHT)));
+        fields.push((product.description.as_str(), DESCRIPTION_WEIGHT));
+
+        let mut words: Vec<(String, f64)> = fields
+            .iter()
+            .flat_map(|(text, weight)| self::words(text).map(|word| (word, *weight)))
+            .collect();
+        // A SKU such as TS-M-RED is also searchable as a whole.
+        if let Some(variant) = &product.variant {
+            words.push((variant.sku.to_lowercase(), SKU_WEIGHT));
+        }
+        Some(Entry {
+            name: product.name.clone(),
+            words,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub name: String,
+    pub score: f64,
+}
+
+// Word index over product names, aliases, SKUs, tags and descriptions, with an entry for
+// each product position. Archived products are left out, so they are neither found nor
+// suggested.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    entries: Vec<Option<Entry>>,
+}
+
+impl SearchIndex {
+    pub fn new(inventory: &[Product]) -> Self {
+        SearchIndex {
+            entries: inventory.iter().map(Entry::of).collect(),
+        }
+    }
+
+    // Indexes the product at a position, replacing the entry of the product that was
+    // there, or adding one after the last product.
+    pub fn set(&mut self, position: usize, product: &Product) {
+        let entry = Entry::of(product);
+        match self.entries.get_mut(position) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    // Products matching every word of the query, best first. A product's score adds up
+    // the best match of each query word, weighted by the field it was found in.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let query: Vec<String> = words(query).collect();
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<SearchHit> = self
+            .entries
+            .iter()
+            .flatten()
+            .filter_map(|entry| {
+                let mut score = 0.0;
+                for term in &query {
+                    let best = entry
+                        .words
+                        .iter()
+                        .map(|(word, weight)| similarity(term, word) * weight)
+                        .fold(0.0, f64::max);
+                    if best == 0.0 {
+                        return None;
+                    }
+                    score += best;
+                }
+                Some(SearchHit {
+                    name: entry.name.clone(),
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        hits
+    }
+
+    // Names of the products closest to a name that matched nothing exactly.
+    pub fn suggest(&self, name: &str) -> Vec<String> {
+        self.search(name)
+            .into_iter()
+            .take(SUGGESTIONS)
+            .map(|hit| hit.name)
+            .collect()
+    }
+}
+
+// The error for a product name that matched nothing, with any close matches.
+pub fn product_not_found<I: Inventory + ?Sized>(name: &str, inventory: &I) -> StoreError {
+    StoreError::NotFound {
+        entity: Entity::Product,
+        name: name.to_string(),
+        product: None,
+        suggestions: inventory.search_index().suggest(name),
+    }
+}
+
+// Two products whose names are close enough that they are likely the same product
+// entered twice. Serialized with the products' names only.
+#[derive(Serialize)]
+pub struct NearDuplicate<'a> {
+    #[serde(serialize_with = "serialize_name")]
+    pub first: &'a Product,
+    #[serde(serialize_with = "serialize_name")]
+    pub second: &'a Product,
+    pub reason: &'static str,
+}
+
+fn serialize_name<S: Serializer>(product: &&Product, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&product.name)
+}
+
+// Pairs of products with names that are the same apart from case, spacing, Unicode form
+// or punctuation, or that differ by a single letter. Such pairs can exist in data saved
+// before names were compared by their key. Variants of the same product are expected to
+// have similar names and are not reported.
+pub fn near_duplicates(inventory: &[Product]) -> Vec<NearDuplicate<'_>> {
+    let compact = |key: &str| {
+        key.chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+    };
+    let related = |a: &Product, b: &Product| {
+        a.is_variant_of(&b.name)
+            || b.is_variant_of(&a.name)
+            || a.variant
+                .as_ref()
+                .is_some_and(|v| b.is_variant_of(&v.parent))
+    };
+
+    let mut duplicates = Vec::new();
+    for (i, first) in inventory.iter().enumerate() {
+        for second in &inventory[i + 1..] {
+            if related(first, second) {
+                continue;
+            }
+            let (a, b) = (name_key(&first.name), name_key(&second.name));
+            let reason = if a == b {
+                "same name apart from case, spacing or Unicode form"
+            } else if compact(&a) == compact(&b) {
+                "same name apart from punctuation and spaces"
+            } else if a.chars().count() >= 5 && strsim::damerau_levenshtein(&a, &b) == 1 {
+                "names differ by one letter"
+            } else {
+                continue;
+            };
+            duplicates.push(NearDuplicate {
+                first,
+                second,
+                reason,
+            });
+        }
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::ProductStatus;
+    use crate::money::Money;
+    use crate::units::Unit;
+
+    fn inventory() -> Result<Vec<Product>, StoreError> {
+        let mut inventory: Vec<Product> = Vec::new();
+        inventory.add_item("Orange Juice", Money::from_cents(150), Money::from_cents(300), 10.into(), Unit::Each, "Freshly squeezed")?;
+        inventory.add_item("Orange", Money::from_cents(20), Money::from_cents(50), 100.into(), Unit::Each, "Citrus fruit")?;
+        inventory.add_item("Lemonade", Money::from_cents(80), Money::from_cents(200), 20.into(), Unit::Each, "Made with orange zest")?;
+        inventory.add_tag("Lemonade", "drinks")?;
+        Ok(inventory)
+    }
+
+    #[test]
+    fn test_search_ranking() -> Result<(), StoreError> {
+        let index = SearchIndex::new(&inventory()?);
+        let names: Vec<String> = index.search("orange").into_iter().map(|hit| hit.name).collect();
+        assert_eq!(names, vec!["Orange", "Orange Juice", "Lemonade"]);
+        let names: Vec<String> = index.search("drink").into_iter().map(|hit| hit.name).collect();
+        assert_eq!(names, vec!["Lemonade"]);
+        assert!(index.search("banana").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_product_not_found_suggestions() -> Result<(), StoreError> {
+        let inventory = inventory()?;
+        assert_eq!(product_not_found("ornage juice", &inventory).to_string(), "Product ornage juice not found. Did you mean Orange Juice?");
+        assert_eq!(product_not_found("Banana", &inventory).to_string(), "Product Banana not found");
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_skips_archived_products() -> Result<(), StoreError> {
+        let mut inventory = inventory()?;
+        inventory.set_status("Orange Juice", ProductStatus::Archived)?;
+        let names: Vec<String> = SearchIndex::new(&inventory).search("orange").into_iter().map(|hit| hit.name).collect();
+        assert_eq!(names, vec!["Orange", "Lemonade"]);
+        assert_eq!(product_not_found("ornage juice", &inventory).to_string(), "Product ornage juice not found");
+        Ok(())
+    }
+
+    #[test]
+    fn test_near_duplicates() -> Result<(), StoreError> {
+        let mut inventory = inventory()?;
+        inventory.add_item("Orange-Juice", Money::from_cents(150), Money::from_cents(300), 1.into(), Unit::Each, "Juice")?;
+        inventory.add_item("Lemonades", Money::from_cents(80), Money::from_cents(200), 1.into(), Unit::Each, "Drink")?;
+        let pairs: Vec<(&str, &str)> = near_duplicates(&inventory).iter().map(|d| (d.first.name.as_str(), d.second.name.as_str())).collect();
+        assert_eq!(pairs, vec![("Orange Juice", "Orange-Juice"), ("Lemonade", "Lemonades")]);
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::attributes::AttributeDefinition;
+use crate::costing::CostingMethod;
+use crate::currency::DEFAULT_CURRENCY;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreSettings {
+    #[serde(default)]
+    pub costing_method: CostingMethod,
+    #[serde(default = "default_base_currency")]
+    pub base_currency: String,
+    #[serde(default)]
+    pub attributes: Vec<AttributeDefinition>,
+}
+
+impl Default for StoreSettings {
+    fn default() -> Self {
+        StoreSettings {
+            costing_method: CostingMethod::default(),
+            base_currency: default_base_currency(),
+            attributes: Vec::new(),
+        }
+    }
+}
+
+fn default_base_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
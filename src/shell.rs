@@ -0,0 +1,217 @@
+use crate::auth::Role;
+use crate::error::StoreError;
+use crate::inventory::{Inventory, name_key};
+use crate::output::{Output, OutputFormat};
+use crate::store::Store;
+use crate::{Cli, execute, execute_dry_run, preview};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+const PROMPT: &str = "store> ";
+const HISTORY_FILE: &str = "data/shell_history.txt";
+
+// Completes subcommand names as the first word and product names after it.
+struct ShellHelper {
+    commands: Vec<String>,
+    products: Vec<String>,
+}
+
+impl ShellHelper {
+    fn new(store: &Store) -> Self {
+        ShellHelper {
+            commands: Cli::command()
+                .get_subcommands()
+                .map(|command| command.get_name().to_string())
+                .chain(["exit".to_string()])
+                .collect(),
+            products: product_names(store),
+        }
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let (start, candidates) = complete_word(&line[..pos], &self.commands, &self.products);
+        let pairs = candidates
+            .into_iter()
+            .map(|name| Pair {
+                replacement: quote(&name),
+                display: name,
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+fn product_names(store: &Store) -> Vec<String> {
+    store
+        .inventory
+        .products()
+        .iter()
+        .map(|product| product.name.clone())
+        .collect()
+}
+
+// Where the word being typed starts in `line`, and the names it could be completed to. A
+// word inside an open quote runs from the quote, so names with spaces can be completed.
+fn complete_word(line: &str, commands: &[String], products: &[String]) -> (usize, Vec<String>) {
+    let start = if line.matches('"').count() % 2 == 1 {
+        line.rfind('"').unwrap_or_default()
+    } else {
+        line.rfind(char::is_whitespace).map_or(0, |i| i + 1)
+    };
+    let word = name_key(line[start..].trim_start_matches('"'));
+    let names = if line[..start].trim().is_empty() {
+        commands
+    } else {
+        products
+    };
+    let candidates = names
+        .iter()
+        .filter(|name| name_key(name).starts_with(&word))
+        .cloned()
+        .collect();
+    (start, candidates)
+}
+
+fn quote(name: &str) -> String {
+    if name.contains(char::is_whitespace) {
+        format!("\"{name}\"")
+    } else {
+        name.to_string()
+    }
+}
+
+fn terminal_error(error: ReadlineError) -> StoreError {
+    StoreError::Storage(format!("Terminal: {error}"))
+}
+
+// Reads commands until exit, quit or end of input, with the user logged in once and the
+// store kept in memory. Commands are written as on the command line without the program
+// name; an --output or --dry-run option on a line applies to that line only. With
+// `dry_run` set, nothing the shell's commands change is saved.
+pub fn run(mut store: Store, role: Role, output: Output, dry_run: bool) -> Result<(), StoreError> {
+    let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::new().map_err(terminal_error)?;
+    editor.set_helper(Some(ShellHelper::new(&store)));
+    // There is no history file before the shell's first run.
+    let _ = editor.load_history(HISTORY_FILE);
+    if output.format == OutputFormat::Table {
+        println!("Type help for a list of commands, or exit to quit.");
+        if dry_run {
+            println!("Dry run: changes are kept until you exit, but not saved.");
+        }
+    }
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => return Err(terminal_error(err)),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line).map_err(terminal_error)?;
+        if matches!(line, "exit" | "quit") {
+            break;
+        }
+        match parse_line(line, output) {
+            Ok(cli) => {
+                run_command(cli, &mut store, role, dry_run);
+                if let Some(helper) = editor.helper_mut() {
+                    helper.products = product_names(&store);
+                }
+            }
+            Err(err) => {
+                let _ = err.print();
+            }
+        }
+    }
+    editor.save_history(HISTORY_FILE).map_err(terminal_error)
+}
+
+// Parses a line of the shell or of a script as a subcommand with its options, quoted as
+// on the command line. The output format is `output`'s unless the line has its own
+// --output option.
+pub fn parse_line(line: &str, output: Output) -> Result<Cli, clap::Error> {
+    let mut cli = Cli::command().no_binary_name(true);
+    let Some(words) = shlex::split(line) else {
+        return Err(cli.error(ErrorKind::ValueValidation, "Unbalanced quotes"));
+    };
+    let matches = cli.try_get_matches_from(words)?;
+    let mut parsed = Cli::from_arg_matches(&matches)?;
+    if matches.value_source("output") != Some(ValueSource::CommandLine) {
+        parsed.output = output.format;
+    }
+    Ok(parsed)
+}
+
+// Runs a command and saves the store if the command can change it. A failed command
+// leaves the store in memory as it was before the command. With --dry-run on the line
+// the command is only previewed; in a dry-run session its changes are kept in memory
+// and shown, but never saved.
+fn run_command(cli: Cli, store: &mut Store, role: Role, dry_run: bool) {
+    let output = Output::new(cli.output);
+    let changes_data = cli.command.changes_data();
+    let result = if cli.dry_run && changes_data {
+        preview(cli.command, store, role, output)
+    } else if changes_data {
+        let before = store.clone();
+        let result = if dry_run {
+            execute_dry_run(cli.command, store, role, output)
+        } else {
+            execute(cli.command, store, role, output).and_then(|()| store.save())
+        };
+        if result.is_err() {
+            *store = before;
+        }
+        result
+    } else {
+        execute(cli.command, store, role, output)
+    };
+    if let Err(err) = result {
+        output.error(&err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_word() {
+        let commands = vec!["record-sale".to_string(), "record-purchase".to_string(), "report".to_string()];
+        let products = vec!["Orange".to_string(), "Orange Juice".to_string(), "Apple".to_string()];
+        assert_eq!(complete_word("rec", &commands, &products), (0, vec!["record-sale".to_string(), "record-purchase".to_string()]));
+        assert_eq!(complete_word("record-sale -n or", &commands, &products), (15, vec!["Orange".to_string(), "Orange Juice".to_string()]));
+        assert_eq!(complete_word("show-product -n \"orange j", &commands, &products), (16, vec!["Orange Juice".to_string()]));
+        assert_eq!(quote("Orange Juice"), "\"Orange Juice\"");
+    }
+}
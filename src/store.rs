@@ -0,0 +1,51 @@
+use crate::category::Category;
+use crate::currency::ExchangeRate;
+use crate::error::StoreError;
+use crate::indexed::IndexedInventory;
+use crate::inventory::Inventory;
+use crate::merge::MergeRecord;
+use crate::persistence::{
+    load_categories, load_exchange_rates, load_inventory, load_merges, load_purchases, load_sales,
+    load_settings, save_categories, save_inventory, save_merges, save_purchases, save_sales,
+    save_settings,
+};
+use crate::purchase::Purchase;
+use crate::sales::Sale;
+use crate::settings::StoreSettings;
+
+// All the data commands work on, loaded from the data files together and saved together.
+// A copy can be taken before a command and put back if the command fails part way.
+#[derive(Debug, Clone, Default)]
+pub struct Store {
+    pub inventory: IndexedInventory,
+    pub sales: Vec<Sale>,
+    pub purchases: Vec<Purchase>,
+    pub settings: StoreSettings,
+    pub exchange_rates: Vec<ExchangeRate>,
+    pub categories: Vec<Category>,
+    pub merges: Vec<MergeRecord>,
+}
+
+impl Store {
+    pub fn load() -> Result<Self, StoreError> {
+        Ok(Store {
+            inventory: IndexedInventory::from(load_inventory()?),
+            sales: load_sales()?,
+            purchases: load_purchases()?,
+            settings: load_settings()?,
+            exchange_rates: load_exchange_rates()?,
+            categories: load_categories()?,
+            merges: load_merges()?,
+        })
+    }
+
+    // Exchange rates are maintained by hand in their file and are never written back.
+    pub fn save(&self) -> Result<(), StoreError> {
+        save_inventory(self.inventory.products())?;
+        save_sales(&self.sales)?;
+        save_purchases(&self.purchases)?;
+        save_settings(&self.settings)?;
+        save_categories(&self.categories)?;
+        save_merges(&self.merges)
+    }
+}
@@ -0,0 +1,882 @@
+use crate::currency::Conversion;
+use crate::error::StoreError;
+use crate::inventory::{Inventory, Product};
+use crate::money::Money;
+use crate::purchase::Purchases;
+use crate::report::Reporter;
+use crate::sales::{Sale, Sales};
+use crate::store::Store;
+use crate::units::{Quantity, Unit};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState, Tabs};
+use ratatui::{DefaultTerminal, Frame};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Screen {
+    Products,
+    Sale,
+    Receiving,
+    Reports,
+}
+
+impl Screen {
+    const ALL: [Screen; 4] = [
+        Screen::Products,
+        Screen::Sale,
+        Screen::Receiving,
+        Screen::Reports,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            Screen::Products => "F1 Products",
+            Screen::Sale => "F2 Sale",
+            Screen::Receiving => "F3 Receiving",
+            Screen::Reports => "F4 Reports",
+        }
+    }
+
+    fn keys(self) -> &'static str {
+        match self {
+            Screen::Products => "Type to search · ↑↓ select · Enter sell · Esc quit",
+            Screen::Sale => {
+                "Tab switch field · Enter add to basket · Del remove line · F5 check out · Esc back"
+            }
+            Screen::Receiving => "Tab next field · ↑↓ pick product · Enter receive · Esc back",
+            Screen::Reports => "←→ switch report · ↑↓ PgUp PgDn scroll · Esc back",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReportKind {
+    Inventory,
+    Sales,
+    Purchases,
+}
+
+impl ReportKind {
+    const ALL: [ReportKind; 3] = [
+        ReportKind::Inventory,
+        ReportKind::Sales,
+        ReportKind::Purchases,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            ReportKind::Inventory => "Inventory",
+            ReportKind::Sales => "Sales",
+            ReportKind::Purchases => "Purchases",
+        }
+    }
+}
+
+// Text typed into a field. The cursor is always at the end.
+#[derive(Debug, Default)]
+struct Input {
+    value: String,
+}
+
+impl Input {
+    // Whether the key edited the text.
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        match key.code {
+            KeyCode::Char(c) => self.value.push(c),
+            KeyCode::Backspace => {
+                self.value.pop();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn clear(&mut self) {
+        self.value.clear();
+    }
+}
+
+// A product in the basket at its retail price when it was added, which is the price
+// charged at checkout even if the retail price changes in the meantime.
+#[derive(Debug)]
+struct BasketLine {
+    product: String,
+    quantity: Quantity,
+    unit: Unit,
+    price: Money,
+}
+
+impl BasketLine {
+    fn total(&self) -> Money {
+        self.price.times(self.quantity)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SaleFocus {
+    Search,
+    Quantity,
+    Basket,
+}
+
+const PRODUCT: usize = 0;
+const QUANTITY: usize = 1;
+const UNIT_COST: usize = 2;
+const RETAIL_PRICE: usize = 3;
+const DESCRIPTION: usize = 4;
+const RECEIVING_FIELDS: [&str; 5] = [
+    "Product",
+    "Quantity",
+    "Unit cost",
+    "Retail price",
+    "Description",
+];
+
+struct Status {
+    message: String,
+    error: bool,
+}
+
+// The terminal interface's state. Keys change the state and every frame is drawn from it,
+// so report panes always show the store as it is. Changes are made to a copy of the store
+// and only kept when the whole sale or purchase succeeds.
+struct App {
+    store: Store,
+    screen: Screen,
+    // Product search shared by the product browser and the sale screen.
+    search: Input,
+    selected: usize,
+    sale_focus: SaleFocus,
+    quantity: Input,
+    basket: Vec<BasketLine>,
+    basket_selected: usize,
+    receiving: [Input; 5],
+    receiving_field: usize,
+    receiving_selected: usize,
+    report: ReportKind,
+    report_scroll: u16,
+    status: Option<Status>,
+    // The store changed since it was last saved.
+    changed: bool,
+    // Changes are kept in memory only and never saved.
+    dry_run: bool,
+    quit: bool,
+}
+
+impl App {
+    fn new(store: Store, dry_run: bool) -> Self {
+        App {
+            store,
+            screen: Screen::Products,
+            search: Input::default(),
+            selected: 0,
+            sale_focus: SaleFocus::Search,
+            quantity: Input::default(),
+            basket: Vec::new(),
+            basket_selected: 0,
+            receiving: Default::default(),
+            receiving_field: PRODUCT,
+            receiving_selected: 0,
+            report: ReportKind::Inventory,
+            report_scroll: 0,
+            status: None,
+            changed: false,
+            dry_run,
+            quit: false,
+        }
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<(), StoreError> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key);
+            }
+            if self.changed {
+                self.changed = false;
+                if self.dry_run {
+                    continue;
+                }
+                if let Err(err) = self.store.save() {
+                    self.error(err.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn info(&mut self, message: String) {
+        self.status = Some(Status {
+            message,
+            error: false,
+        });
+    }
+
+    fn error(&mut self, message: String) {
+        self.status = Some(Status {
+            message,
+            error: true,
+        });
+    }
+
+    // Products matching a search, best match first; all listed products when the search
+    // is empty. Archived products cannot be sold and are left out.
+    fn matches(&self, query: &str) -> Vec<&Product> {
+        let inventory = &self.store.inventory;
+        let products: Vec<&Product> = if query.trim().is_empty() {
+            inventory.products().iter().collect()
+        } else {
+            inventory
+                .search_index()
+                .search(query)
+                .iter()
+                .filter_map(|hit| inventory.get_item(&hit.name))
+                .collect()
+        };
+        products.into_iter().filter(|p| !p.is_archived()).collect()
+    }
+
+    fn selected_product(&self) -> Option<&Product> {
+        let matches = self.matches(&self.search.value);
+        matches
+            .get(self.selected.min(matches.len().saturating_sub(1)))
+            .copied()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if let KeyCode::F(n @ 1..=4) = key.code {
+            self.screen = Screen::ALL[usize::from(n) - 1];
+            self.status = None;
+            return;
+        }
+        match self.screen {
+            Screen::Products => self.products_key(key),
+            Screen::Sale => self.sale_key(key),
+            Screen::Receiving => self.receiving_key(key),
+            Screen::Reports => self.reports_key(key),
+        }
+    }
+
+    fn products_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected += 1,
+            KeyCode::Enter if self.selected_product().is_some() => {
+                self.screen = Screen::Sale;
+                self.sale_focus = SaleFocus::Quantity;
+            }
+            _ => {
+                if self.search.handle(key) {
+                    self.selected = 0;
+                }
+            }
+        }
+        self.selected = self
+            .selected
+            .min(self.matches(&self.search.value).len().saturating_sub(1));
+    }
+
+    fn sale_key(&mut self, key: KeyEvent) {
+        match (key.code, self.sale_focus) {
+            (KeyCode::Esc, _) => self.screen = Screen::Products,
+            (KeyCode::F(5), _) => self.checkout(),
+            (KeyCode::Tab, focus) | (KeyCode::BackTab, focus) => {
+                let order = [SaleFocus::Search, SaleFocus::Quantity, SaleFocus::Basket];
+                let position = order.iter().position(|f| *f == focus).unwrap_or_default();
+                let step = if key.code == KeyCode::Tab { 1 } else { 2 };
+                self.sale_focus = order[(position + step) % order.len()];
+            }
+            (KeyCode::Enter, SaleFocus::Search | SaleFocus::Quantity) => self.add_to_basket(),
+            (KeyCode::Up, SaleFocus::Basket) => {
+                self.basket_selected = self.basket_selected.saturating_sub(1)
+            }
+            (KeyCode::Down, SaleFocus::Basket) => {
+                self.basket_selected =
+                    (self.basket_selected + 1).min(self.basket.len().saturating_sub(1))
+            }
+            (KeyCode::Delete | KeyCode::Backspace, SaleFocus::Basket) => {
+                if self.basket_selected < self.basket.len() {
+                    let line = self.basket.remove(self.basket_selected);
+                    self.basket_selected = self
+                        .basket_selected
+                        .min(self.basket.len().saturating_sub(1));
+                    self.info(format!("Removed {} from the basket", line.product));
+                }
+            }
+            (_, SaleFocus::Basket) => {}
+            (_, SaleFocus::Quantity) => {
+                self.quantity.handle(key);
+            }
+            (KeyCode::Up, SaleFocus::Search) => self.selected = self.selected.saturating_sub(1),
+            (KeyCode::Down, SaleFocus::Search) => {
+                self.selected = (self.selected + 1)
+                    .min(self.matches(&self.search.value).len().saturating_sub(1))
+            }
+            (_, SaleFocus::Search) => {
+                if self.search.handle(key) {
+                    self.selected = 0;
+                }
+            }
+        }
+    }
+
+    // Adds the selected product to the basket, by default one of it in its own unit.
+    fn add_to_basket(&mut self) {
+        let quantity = match self.quantity.value.trim() {
+            "" => Quantity::from(1),
+            value => match value.parse::<Quantity>() {
+                Ok(quantity) if !quantity.is_zero() && quantity.as_decimal().is_sign_positive() => {
+                    quantity
+                }
+                _ => return self.error(format!("Invalid quantity: {value}")),
+            },
+        };
+        let Some(product) = self.selected_product() else {
+            return self.error("No product selected".to_string());
+        };
+        let (name, unit, price) = (product.name.clone(), product.unit, product.retail_price);
+        match self.basket.iter_mut().find(|line| line.product == name) {
+            Some(line) => line.quantity += quantity,
+            None => self.basket.push(BasketLine {
+                product: name.clone(),
+                quantity,
+                unit,
+                price,
+            }),
+        }
+        self.quantity.clear();
+        self.search.clear();
+        self.selected = 0;
+        self.sale_focus = SaleFocus::Search;
+        self.info(format!("Added {quantity} {unit} of {name}"));
+    }
+
+    // Records a sale for each basket line. If any line cannot be sold, e.g. for lack of
+    // stock, none of them is recorded and the basket is kept.
+    fn checkout(&mut self) {
+        if self.basket.is_empty() {
+            return self.error("The basket is empty".to_string());
+        }
+        let mut store = self.store.clone();
+        let conversion = Conversion::base(&store.settings.base_currency);
+        let costing_method = store.settings.costing_method;
+        let sales: Result<Vec<Sale>, StoreError> = self
+            .basket
+            .iter()
+            .map(|line| {
+                store.sales.record_sale(
+                    &line.product,
+                    line.quantity,
+                    None,
+                    Some(line.price),
+                    &conversion,
+                    costing_method,
+                    &mut store.inventory,
+                )
+            })
+            .collect();
+        match sales {
+            Ok(sales) => {
+                let total: Money = sales.iter().map(|sale| sale.base_total).sum();
+                let base = store.settings.base_currency.clone();
+                self.store = store;
+                self.basket.clear();
+                self.basket_selected = 0;
+                self.changed = true;
+                self.info(format!(
+                    "Sale recorded: {} lines, total {total} {base}",
+                    sales.len()
+                ));
+            }
+            Err(err) => self.error(format!("Sale not recorded: {err}")),
+        }
+    }
+
+    fn receiving_key(&mut self, key: KeyEvent) {
+        let matches = self.matches(&self.receiving[PRODUCT].value).len();
+        match key.code {
+            KeyCode::Esc => self.screen = Screen::Products,
+            KeyCode::Enter => self.receive(),
+            KeyCode::Tab => {
+                if self.receiving_field == PRODUCT {
+                    self.pick_product();
+                }
+                self.receiving_field = (self.receiving_field + 1) % RECEIVING_FIELDS.len();
+            }
+            KeyCode::BackTab => {
+                self.receiving_field =
+                    (self.receiving_field + RECEIVING_FIELDS.len() - 1) % RECEIVING_FIELDS.len();
+            }
+            KeyCode::Up if self.receiving_field == PRODUCT => {
+                self.receiving_selected = self.receiving_selected.saturating_sub(1)
+            }
+            KeyCode::Down if self.receiving_field == PRODUCT => {
+                self.receiving_selected =
+                    (self.receiving_selected + 1).min(matches.saturating_sub(1))
+            }
+            _ => {
+                if self.receiving[self.receiving_field].handle(key)
+                    && self.receiving_field == PRODUCT
+                {
+                    self.receiving_selected = 0;
+                }
+            }
+        }
+    }
+
+    // Fills the form from the highlighted product, unless the typed name is already an
+    // existing product or no product matches, in which case a new product is received.
+    fn pick_product(&mut self) {
+        let typed = &self.receiving[PRODUCT].value;
+        if typed.trim().is_empty() || self.store.inventory.get_item(typed).is_some() {
+            return;
+        }
+        let matches = self.matches(typed);
+        let Some(product) = matches.get(self.receiving_selected) else {
+            return;
+        };
+        let (name, description) = (product.name.clone(), product.description.clone());
+        self.receiving[PRODUCT].value = name;
+        if self.receiving[DESCRIPTION].value.is_empty() {
+            self.receiving[DESCRIPTION].value = description;
+        }
+    }
+
+    fn receive(&mut self) {
+        let field = |index: usize| self.receiving[index].value.trim();
+        let product = field(PRODUCT).to_string();
+        let quantity = match field(QUANTITY).parse::<Quantity>() {
+            Ok(quantity) => quantity,
+            Err(err) => return self.error(err),
+        };
+        let cost = match field(UNIT_COST).parse::<Money>() {
+            Ok(cost) => cost,
+            Err(err) => return self.error(err),
+        };
+        let retail_price = match field(RETAIL_PRICE) {
+            "" => None,
+            price => match price.parse::<Money>() {
+                Ok(price) => Some(price),
+                Err(err) => return self.error(err),
+            },
+        };
+        let description = match field(DESCRIPTION) {
+            "" => self
+                .store
+                .inventory
+                .get_item(&product)
+                .map(|p| p.description.clone())
+                .unwrap_or_default(),
+            description => description.to_string(),
+        };
+
+        let mut store = self.store.clone();
+        let conversion = Conversion::base(&store.settings.base_currency);
+        match store.purchases.record_purchase(
+            &product,
+            quantity,
+            None,
+            None,
+            cost,
+            retail_price,
+            &description,
+            &conversion,
+            store.settings.costing_method,
+            &mut store.inventory,
+        ) {
+            Ok(purchase) => {
+                self.info(format!(
+                    "Received {} {} of {} for {} {}",
+                    purchase.quantity,
+                    purchase.unit,
+                    purchase.product_name,
+                    purchase.total_cost,
+                    purchase.currency
+                ));
+                self.store = store;
+                self.changed = true;
+                self.receiving.iter_mut().for_each(Input::clear);
+                self.receiving_field = PRODUCT;
+                self.receiving_selected = 0;
+            }
+            Err(err) => self.error(format!("Purchase not recorded: {err}")),
+        }
+    }
+
+    fn reports_key(&mut self, key: KeyEvent) {
+        let position = ReportKind::ALL
+            .iter()
+            .position(|kind| *kind == self.report)
+            .unwrap_or_default();
+        match key.code {
+            KeyCode::Esc => self.screen = Screen::Products,
+            KeyCode::Left | KeyCode::Right => {
+                let step = if key.code == KeyCode::Right { 1 } else { 2 };
+                self.report = ReportKind::ALL[(position + step) % ReportKind::ALL.len()];
+                self.report_scroll = 0;
+            }
+            KeyCode::Up => self.report_scroll = self.report_scroll.saturating_sub(1),
+            KeyCode::Down => self.report_scroll = self.report_scroll.saturating_add(1),
+            KeyCode::PageUp => self.report_scroll = self.report_scroll.saturating_sub(10),
+            KeyCode::PageDown => self.report_scroll = self.report_scroll.saturating_add(10),
+            _ => {}
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [tabs, body, status] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let selected = Screen::ALL
+            .iter()
+            .position(|screen| *screen == self.screen)
+            .unwrap_or_default();
+        frame.render_widget(
+            Tabs::new(Screen::ALL.map(Screen::title))
+                .select(selected)
+                .highlight_style(Style::new().reversed()),
+            tabs,
+        );
+
+        match self.screen {
+            Screen::Products => self.draw_products(frame, body),
+            Screen::Sale => self.draw_sale(frame, body),
+            Screen::Receiving => self.draw_receiving(frame, body),
+            Screen::Reports => self.draw_reports(frame, body),
+        }
+
+        let line = match &self.status {
+            Some(status) if status.error => Line::from(status.message.as_str()).fg(Color::Red),
+            Some(status) => Line::from(status.message.as_str()).fg(Color::Green),
+            None if self.dry_run => Line::from(format!(
+                "{} · Dry run, nothing is saved · Ctrl-C quit",
+                self.screen.keys()
+            ))
+            .dim(),
+            None => Line::from(format!("{} · Ctrl-C quit", self.screen.keys())).dim(),
+        };
+        frame.render_widget(line, status);
+    }
+
+    fn draw_input(&self, frame: &mut Frame, area: Rect, title: &str, input: &Input, focused: bool) {
+        let style = if focused {
+            Style::new().fg(Color::Yellow)
+        } else {
+            Style::new()
+        };
+        let text = if focused {
+            format!("{}▏", input.value)
+        } else {
+            input.value.clone()
+        };
+        frame.render_widget(
+            Paragraph::new(text).block(Block::bordered().title(title).border_style(style)),
+            area,
+        );
+    }
+
+    fn product_table<'a>(&self, products: &[&'a Product], title: &'a str) -> Table<'a> {
+        let base = &self.store.settings.base_currency;
+        let rows = products.iter().map(|product| {
+            Row::new([
+                product.name.clone(),
+                product
+                    .variant
+                    .as_ref()
+                    .map(|v| v.sku.clone())
+                    .unwrap_or_default(),
+                format!("{} {}", product.quantity, product.unit),
+                format!("{} {base}", product.retail_price),
+            ])
+        });
+        Table::new(
+            rows,
+            [
+                Constraint::Fill(3),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(["Name", "SKU", "Stock", "Retail"]).bold())
+        .row_highlight_style(Style::new().reversed())
+        .block(Block::bordered().title(title))
+    }
+
+    fn draw_products(&self, frame: &mut Frame, area: Rect) {
+        let [search, main] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0)]).areas(area);
+        let [list, details] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main);
+        self.draw_input(frame, search, "Search", &self.search, true);
+
+        let products = self.matches(&self.search.value);
+        let mut state = TableState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(self.product_table(&products, "Products"), list, &mut state);
+
+        let lines: Vec<Line> = self
+            .selected_product()
+            .map(|product| {
+                Reporter::product_details(product, &self.store.settings.base_currency)
+                    .into_iter()
+                    .map(|(label, value)| Line::from(format!("{label}: {value}")))
+                    .collect()
+            })
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Details")),
+            details,
+        );
+    }
+
+    fn draw_sale(&self, frame: &mut Frame, area: Rect) {
+        let [search, main, quantity] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .areas(area);
+        let [list, basket] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(main);
+        self.draw_input(
+            frame,
+            search,
+            "Search",
+            &self.search,
+            self.sale_focus == SaleFocus::Search,
+        );
+        self.draw_input(
+            frame,
+            quantity,
+            "Quantity (default 1)",
+            &self.quantity,
+            self.sale_focus == SaleFocus::Quantity,
+        );
+
+        let products = self.matches(&self.search.value);
+        let mut state = TableState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(self.product_table(&products, "Products"), list, &mut state);
+
+        let base = &self.store.settings.base_currency;
+        let total: Money = self.basket.iter().map(BasketLine::total).sum();
+        let rows = self.basket.iter().map(|line| {
+            Row::new([
+                line.product.clone(),
+                format!("{} {}", line.quantity, line.unit),
+                format!("{} {base}", line.price),
+                format!("{} {base}", line.total()),
+            ])
+        });
+        let border = if self.sale_focus == SaleFocus::Basket {
+            Style::new().fg(Color::Yellow)
+        } else {
+            Style::new()
+        };
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(3),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(["Product", "Qty", "Price", "Total"]).bold())
+        .row_highlight_style(Style::new().reversed())
+        .block(
+            Block::bordered()
+                .title(format!("Basket · total {total} {base}"))
+                .border_style(border),
+        );
+        let mut state = TableState::default()
+            .with_selected((self.sale_focus == SaleFocus::Basket).then_some(self.basket_selected));
+        frame.render_stateful_widget(table, basket, &mut state);
+    }
+
+    fn draw_receiving(&self, frame: &mut Frame, area: Rect) {
+        let [form, list] = Layout::vertical([
+            Constraint::Length(RECEIVING_FIELDS.len() as u16 + 2),
+            Constraint::Min(0),
+        ])
+        .areas(area);
+
+        let width = RECEIVING_FIELDS
+            .iter()
+            .map(|f| f.len())
+            .max()
+            .unwrap_or_default()
+            + 1;
+        let lines: Vec<Line> = RECEIVING_FIELDS
+            .iter()
+            .zip(&self.receiving)
+            .enumerate()
+            .map(|(index, (label, input))| {
+                let label = format!("{label}:");
+                if index == self.receiving_field {
+                    Line::from(format!("{label:<width$} {}▏", input.value)).fg(Color::Yellow)
+                } else {
+                    Line::from(format!("{label:<width$} {}", input.value))
+                }
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Receive stock")),
+            form,
+        );
+
+        let products = self.matches(&self.receiving[PRODUCT].value);
+        let mut state = TableState::default()
+            .with_selected((self.receiving_field == PRODUCT).then_some(self.receiving_selected));
+        frame.render_stateful_widget(
+            self.product_table(&products, "Matching products (Tab picks)"),
+            list,
+            &mut state,
+        );
+    }
+
+    fn draw_reports(&self, frame: &mut Frame, area: Rect) {
+        let store = &self.store;
+        let base = &store.settings.base_currency;
+        let report = match self.report {
+            ReportKind::Inventory => {
+                Reporter::generate_inventory_report(store.inventory.products(), base, None)
+            }
+            ReportKind::Sales => Reporter::generate_sales_report(&store.sales, base, None),
+            ReportKind::Purchases => {
+                Reporter::generate_purchase_report(&store.purchases, base, None)
+            }
+        };
+        let titles: Vec<String> = ReportKind::ALL
+            .iter()
+            .map(|kind| {
+                if *kind == self.report {
+                    format!("[{}]", kind.title())
+                } else {
+                    kind.title().to_string()
+                }
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(report)
+                .scroll((self.report_scroll, 0))
+                .block(Block::bordered().title(titles.join(" "))),
+            area,
+        );
+    }
+}
+
+// Runs the full-screen interface until the user quits. The store is saved after every
+// sale and purchase, unless `dry_run` is set.
+pub fn run(store: Store, dry_run: bool) -> Result<(), StoreError> {
+    let mut terminal =
+        ratatui::try_init().map_err(|err| StoreError::Storage(format!("Terminal: {err}")))?;
+    let result = App::new(store, dry_run).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::costing::CostingMethod;
+    use crate::inventory::ProductUpdate;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        text.chars().for_each(|c| app.handle_key(key(KeyCode::Char(c))));
+    }
+
+    fn app() -> Result<App, StoreError> {
+        let mut store = Store::default();
+        store.inventory.add_item("Orange", Money::from_cents(50), Money::from_cents(75), 10.into(), Unit::Each, "Fruit")?;
+        store.inventory.add_item("Apple", Money::from_cents(40), Money::from_cents(60), 2.into(), Unit::Each, "Fruit")?;
+        Ok(App::new(store, false))
+    }
+
+    #[test]
+    fn test_sale_basket_checkout() -> Result<(), StoreError> {
+        let mut app = app()?;
+        app.handle_key(key(KeyCode::F(2)));
+        type_text(&mut app, "orange");
+        app.handle_key(key(KeyCode::Tab));
+        type_text(&mut app, "4");
+        app.handle_key(key(KeyCode::Enter));
+        type_text(&mut app, "apple");
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.basket.len(), 2);
+
+        // A price change after a line is added does not change what the line is charged.
+        app.store.inventory.update_item("Orange", ProductUpdate { retail_price: Some(Money::from_cents(90)), ..Default::default() }, CostingMethod::Fifo)?;
+        app.handle_key(key(KeyCode::F(5)));
+        assert_eq!(app.store.sales.len(), 2);
+        assert_eq!(app.store.sales[0].sale_price, Money::from_cents(75));
+        assert_eq!(app.store.inventory.get_item("Orange").unwrap().quantity, 6.into());
+        assert!(app.basket.is_empty() && app.changed);
+
+        // Three apples are more than in stock, so neither line is sold.
+        type_text(&mut app, "orange");
+        app.handle_key(key(KeyCode::Enter));
+        type_text(&mut app, "apple");
+        app.handle_key(key(KeyCode::Tab));
+        type_text(&mut app, "3");
+        app.handle_key(key(KeyCode::Enter));
+        app.handle_key(key(KeyCode::F(5)));
+        assert_eq!(app.store.sales.len(), 2);
+        assert_eq!(app.store.inventory.get_item("Orange").unwrap().quantity, 6.into());
+        assert_eq!(app.basket.len(), 2);
+        assert!(app.status.as_ref().is_some_and(|s| s.error));
+        Ok(())
+    }
+
+    #[test]
+    fn test_receive_stock() -> Result<(), StoreError> {
+        let mut app = app()?;
+        app.handle_key(key(KeyCode::F(3)));
+        type_text(&mut app, "oran");
+        app.handle_key(key(KeyCode::Tab));
+        type_text(&mut app, "5");
+        app.handle_key(key(KeyCode::Tab));
+        type_text(&mut app, "0.55");
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.store.purchases.len(), 1);
+        let orange = app.store.inventory.get_item("Orange").unwrap();
+        assert_eq!((orange.quantity, orange.cost_price), (15.into(), Money::from_cents(55)));
+        assert!(app.receiving.iter().all(|input| input.value.is_empty()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_draw_screens() -> Result<(), Box<dyn std::error::Error>> {
+        let mut app = app()?;
+        let mut terminal = ratatui::Terminal::new(ratatui::backend::TestBackend::new(100, 30))?;
+        for screen in 1..=4 {
+            app.handle_key(key(KeyCode::F(screen)));
+            terminal.draw(|frame| app.draw(frame))?;
+        }
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("INVENTORY REPORT"));
+        app.handle_key(key(KeyCode::F(1)));
+        terminal.draw(|frame| app.draw(frame))?;
+        let screen = format!("{:?}", terminal.backend().buffer());
+        assert!(screen.contains("Orange") && screen.contains("Description: Fruit"));
+        Ok(())
+    }
+}
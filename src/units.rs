@@ -0,0 +1,302 @@
+use crate::error::{Entity, StoreError};
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
+use std::str::FromStr;
+use validator::ValidationError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    #[default]
+    Each,
+    Kg,
+    G,
+    Litre,
+    Metre,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    Count,
+    Mass,
+    Volume,
+    Length,
+}
+
+impl Unit {
+    // Number of decimal places a quantity in this unit may have.
+    pub fn precision(&self) -> u32 {
+        match self {
+            Unit::Each | Unit::G => 0,
+            Unit::Kg | Unit::Litre => 3,
+            Unit::Metre => 2,
+        }
+    }
+
+    fn dimension(&self) -> Dimension {
+        match self {
+            Unit::Each => Dimension::Count,
+            Unit::Kg | Unit::G => Dimension::Mass,
+            Unit::Litre => Dimension::Volume,
+            Unit::Metre => Dimension::Length,
+        }
+    }
+
+    // Size of this unit in the smallest unit of its dimension.
+    fn factor(&self) -> Decimal {
+        match self {
+            Unit::Kg => Decimal::ONE_THOUSAND,
+            _ => Decimal::ONE,
+        }
+    }
+
+    // How many of `self` make up one `to`, e.g. 1000 for grams into kilograms.
+    pub fn ratio_to(&self, to: Unit) -> Result<Decimal, StoreError> {
+        if self.dimension() != to.dimension() {
+            return Err(StoreError::invalid(format!(
+                "Cannot convert {self} to {to}"
+            )));
+        }
+        Ok(to.factor() / self.factor())
+    }
+
+    pub fn convert(&self, quantity: Quantity, to: Unit) -> Result<Quantity, StoreError> {
+        let ratio = self.ratio_to(to)?;
+        let converted = Quantity(quantity.0 / ratio);
+        converted.check_precision(to)?;
+        Ok(converted.normalize())
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self {
+            Unit::Each => "each",
+            Unit::Kg => "kg",
+            Unit::G => "g",
+            Unit::Litre => "litre",
+            Unit::Metre => "metre",
+        };
+        write!(f, "{unit}")
+    }
+}
+
+// A named packaging of a product, e.g. a case of 24. `size` counts packs of `of` when it
+// is set and the product's own unit otherwise, so a pallet can be 40 cases.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Pack {
+    pub name: String,
+    pub size: Quantity,
+    #[serde(default)]
+    pub of: Option<String>,
+}
+
+// Number of the product's own units in one `name` pack.
+pub fn pack_size(packs: &[Pack], name: &str) -> Result<Quantity, StoreError> {
+    let mut size = Quantity::from(1);
+    let mut current = name;
+    // Each pack can appear at most once in a chain, so a longer one has a cycle.
+    for _ in 0..=packs.len() {
+        let pack = packs
+            .iter()
+            .find(|p| p.name == current)
+            .ok_or_else(|| StoreError::not_found(Entity::Pack, current))?;
+        size = size * pack.size;
+        match &pack.of {
+            Some(of) => current = of,
+            None => return Ok(size),
+        }
+    }
+    Err(StoreError::invalid(format!(
+        "Pack {name} is defined in terms of itself"
+    )))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Quantity(Decimal);
+
+impl Quantity {
+    pub const ZERO: Quantity = Quantity(Decimal::ZERO);
+
+    pub fn as_decimal(&self) -> Decimal {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn check_precision(&self, unit: Unit) -> Result<(), StoreError> {
+        if self.0.normalize().scale() > unit.precision() {
+            return Err(StoreError::invalid_field(
+                "quantity",
+                format!(
+                    "Quantity {} {unit} has more than {} decimal places",
+                    self.0.normalize(),
+                    unit.precision()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn normalize(self) -> Self {
+        Quantity(self.0.normalize())
+    }
+}
+
+pub fn validate_non_negative_quantity(value: &Quantity) -> Result<(), ValidationError> {
+    if value.0.is_sign_negative() && !value.0.is_zero() {
+        return Err(ValidationError::new("range"));
+    }
+    Ok(())
+}
+
+pub fn validate_positive_quantity(value: &Quantity) -> Result<(), ValidationError> {
+    if value.0 <= Decimal::ZERO {
+        return Err(ValidationError::new("range"));
+    }
+    Ok(())
+}
+
+impl From<u32> for Quantity {
+    fn from(value: u32) -> Self {
+        Quantity(Decimal::from(value))
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.normalize())
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Decimal::from_str(s.trim())
+            .map(|value| Quantity(value.normalize()))
+            .map_err(|_| format!("Invalid quantity: {s}"))
+    }
+}
+
+impl Add for Quantity {
+    type Output = Quantity;
+
+    fn add(self, other: Quantity) -> Quantity {
+        Quantity(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Quantity {
+    fn add_assign(&mut self, other: Quantity) {
+        self.0 += other.0;
+    }
+}
+
+impl Mul for Quantity {
+    type Output = Quantity;
+
+    fn mul(self, other: Quantity) -> Quantity {
+        Quantity(self.0 * other.0).normalize()
+    }
+}
+
+impl Sub for Quantity {
+    type Output = Quantity;
+
+    fn sub(self, other: Quantity) -> Quantity {
+        Quantity(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Quantity {
+    fn sub_assign(&mut self, other: Quantity) {
+        self.0 -= other.0;
+    }
+}
+
+impl Sum for Quantity {
+    fn sum<I: Iterator<Item = Quantity>>(iter: I) -> Quantity {
+        iter.fold(Quantity::ZERO, Add::add)
+    }
+}
+
+// Quantities are written as JSON strings, like amounts, so fractional stock round-trips
+// exactly. Files written before units existed hold whole numbers, which are still read.
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0.normalize().to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QuantityVisitor;
+
+        impl Visitor<'_> for QuantityVisitor {
+            type Value = Quantity;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a decimal quantity as a string or number")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Quantity, E> {
+                Quantity::from_str(value).map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Quantity, E> {
+                self.visit_str(&value.to_string())
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Quantity, E> {
+                Ok(Quantity(Decimal::from(value)))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Quantity, E> {
+                Ok(Quantity(Decimal::from(value)))
+            }
+        }
+
+        deserializer.deserialize_any(QuantityVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_grams_to_kilograms() {
+        let grams: Quantity = "1250".parse().unwrap();
+        assert_eq!(Unit::G.convert(grams, Unit::Kg).unwrap(), "1.25".parse().unwrap());
+        assert!(Unit::Kg.convert(grams, Unit::Litre).is_err());
+    }
+
+    #[test]
+    fn test_quantity_precision() {
+        let half: Quantity = "0.5".parse().unwrap();
+        assert!(half.check_precision(Unit::Each).is_err());
+        assert!(half.check_precision(Unit::Kg).is_ok());
+        assert!(Unit::Kg.convert(half, Unit::G).is_ok());
+        assert!(Unit::G.convert(half, Unit::Kg).is_err());
+        assert!(Unit::G.convert("0.5".parse().unwrap(), Unit::G).is_err());
+    }
+
+    #[test]
+    fn test_nested_pack_size() {
+        let packs = vec![
+            Pack { name: "case".to_string(), size: 24.into(), of: None },
+            Pack { name: "pallet".to_string(), size: 40.into(), of: Some("case".to_string()) },
+        ];
+        assert_eq!(pack_size(&packs, "case").unwrap(), 24.into());
+        assert_eq!(pack_size(&packs, "pallet").unwrap(), 960.into());
+        assert!(pack_size(&packs, "crate").is_err());
+    }
+}